@@ -0,0 +1,98 @@
+#!/usr/bin/env python3
+"""Generates src/unicode.txt, the table of characters used by the unicode mode.
+
+Usage: generate-unicode-table.py emoji-test.txt annotations.xml... > src/unicode.txt
+
+The files come from:
+  https://www.unicode.org/Public/emoji/latest/emoji-test.txt
+  https://github.com/unicode-org/cldr/blob/main/common/annotations/en.xml
+  https://github.com/unicode-org/cldr/blob/main/common/annotationsDerived/en.xml
+
+The names of the other characters are taken from Python's copy of UnicodeData.txt,
+so the Unicode version depends on the Python version used.
+
+Every line of the table is `characters<TAB>name<TAB>keywords`, with the keywords separated by `|`.
+Emoji come first, in the order of emoji-test.txt, followed by the other characters by code point.
+"""
+
+import sys
+import unicodedata
+import xml.etree.ElementTree as ElementTree
+
+# letters are only included from these scripts, the others are rarely typed by name
+LETTER_SCRIPTS = ("LATIN", "GREEK", "CYRILLIC")
+# makes the preceding character an emoji, but isn't needed to look it up
+VARIATION_SELECTOR = "\ufe0f"
+
+
+def read_emoji(path):
+    emoji = []
+    with open(path, encoding="utf-8") as file:
+        for line in file:
+            if line.startswith("#") or ";" not in line:
+                continue
+            codes, rest = line.split(";", 1)
+            status, comment = rest.split("#", 1)
+            if status.strip() != "fully-qualified":
+                continue
+            characters = "".join(chr(int(code, 16)) for code in codes.split())
+            # the comment is `<emoji> E<version> <name>`
+            name = comment.strip().split(" ", 2)[2]
+            # the skin tone variants would only clutter the list
+            if "skin tone" in name:
+                continue
+            emoji.append((characters, name))
+    return emoji
+
+
+def read_annotations(paths):
+    keywords = {}
+    for path in paths:
+        for annotation in ElementTree.parse(path).getroot().iter("annotation"):
+            # the other type is the name, which is already known
+            if annotation.get("type") == "tts":
+                continue
+            characters = annotation.get("cp").replace(VARIATION_SELECTOR, "")
+            keywords.setdefault(characters, []).extend(
+                k.strip() for k in annotation.text.split("|")
+            )
+    return keywords
+
+
+def included(character):
+    category = unicodedata.category(character)
+    if category[0] in "SP" or category in ("No", "Nl"):
+        return True
+    return category[0] == "L" and unicodedata.name(character).startswith(LETTER_SCRIPTS)
+
+
+def main():
+    # without the annotations the keyword column would silently be empty
+    if len(sys.argv) < 3:
+        sys.exit(__doc__)
+    emoji = read_emoji(sys.argv[1])
+    keywords = read_annotations(sys.argv[2:])
+    if not keywords:
+        sys.exit("No annotations found in {}".format(", ".join(sys.argv[2:])))
+
+    lines = []
+    seen = set()
+    for characters, name in emoji:
+        key = characters.replace(VARIATION_SELECTOR, "")
+        seen.add(key)
+        lines.append((characters, name, keywords.get(key, [])))
+    # ASCII is typed directly
+    for code in range(0xA0, 0x110000):
+        character = chr(code)
+        name = unicodedata.name(character, None)
+        if name is None or character in seen or not included(character):
+            continue
+        lines.append((character, name.lower(), keywords.get(character, [])))
+
+    for characters, name, words in lines:
+        words = [w for w in dict.fromkeys(words) if w != name]
+        print("{}\t{}\t{}".format(characters, name, "|".join(words)))
+
+
+if __name__ == "__main__":
+    main()
@@ -1,5 +1,7 @@
+use crate::completion::escape;
 use std::env::var;
 use std::fs::{read_dir, read_to_string};
+use std::path::Path;
 use std::sync::Mutex;
 use std::time::Instant;
 use std::collections::BTreeMap;
@@ -10,7 +12,53 @@ type DirID = String;
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct App {
     pub exec: String,
+    pub comment: String,
     pub show_terminal: bool,
+    /// The WM_CLASS of the application's windows, if known
+    pub wm_class: String,
+    /// Whether the application never has more than one main window
+    pub single_main_window: bool,
+    /// The desktop file ID, like `org.gnome.Evince.desktop`, empty for commands in the PATH
+    pub id: String,
+    /// The MIME types of the files the application can open
+    pub mime_types: Vec<String>,
+    /// The Exec line with its field codes, which say where the files go
+    pub exec_template: String,
+}
+
+impl App {
+    /// The command to open the file with the application
+    pub fn command_with_file(&self, path: &Path) -> String {
+        exec_with_file(&self.exec_template, path)
+    }
+}
+
+/// Puts the file where the Exec line has a field code for files or URLs, or at the end if
+/// there is none
+pub fn exec_with_file(exec: &str, path: &Path) -> String {
+    let file = escape(&path.to_string_lossy());
+    let mut command = String::new();
+    let mut used = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            command.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('f') | Some('F') | Some('u') | Some('U') if !used => {
+                command += &file;
+                used = true;
+            }
+            Some('%') => command.push('%'),
+            // the other field codes are left out
+            _ => {}
+        }
+    }
+    if !used {
+        command = format!("{} {}", command.trim_end(), file);
+    }
+    command
 }
 
 pub fn read_applications(apps: &Mutex<Apps>, scan_path: bool, progress: &Mutex<(u32, u32)>) {
@@ -117,13 +165,14 @@ pub fn read_applications(apps: &Mutex<Apps>, scan_path: bool, progress: &Mutex<(
         scan_path_dirs(apps, progress);
     }
 
-    println!(
+    eprintln!(
         "Finished reading all {} applications ({}s)",
         files_to_scan,
         now.elapsed().as_secs_f64()
     );
 }
 
+#[allow(clippy::manual_strip, clippy::comparison_to_empty)]
 fn scan_desktop_entries(
     apps: &Mutex<Apps>,
     dirs: Vec<(String, DirID)>,
@@ -131,7 +180,7 @@ fn scan_desktop_entries(
 ) {
     let mut scanned_ids = Vec::new();
     for dir in dirs {
-        println!("scanning {:?}", dir);
+        eprintln!("scanning {:?}", dir);
         'files: for file in read_dir(dir.0).unwrap() {
             let file = file.unwrap();
 
@@ -167,7 +216,7 @@ fn scan_desktop_entries(
             if scanned_ids.contains(&file_id) {
                 continue;
             }
-            scanned_ids.push(file_id);
+            scanned_ids.push(file_id.clone());
 
             // cool. now we can start parsing the file
             let contents = match read_to_string(path) {
@@ -177,8 +226,13 @@ fn scan_desktop_entries(
 
             let mut name = String::new();
             let mut exec = String::new();
+            let mut comment = String::new();
             let mut app_type = String::new();
             let mut terminal = String::new();
+            let mut wm_class = String::new();
+            let mut single_main_window = String::new();
+            let mut exec_template = String::new();
+            let mut mime_types = String::new();
             for line in contents.lines() {
                 if line.starts_with("Hidden=") {
                     let mut value = line[7..].to_string();
@@ -201,6 +255,7 @@ fn scan_desktop_entries(
                         _ => {}
                     }
                 } else if exec == "" && line.starts_with("Exec=") {
+                    exec_template = line[5..].trim().to_string();
                     exec = line[5..].to_string();
                     // remove any arguments
                     while let Some(i) = exec.find('%') {
@@ -211,10 +266,20 @@ fn scan_desktop_entries(
                 } else if name == "" && line.starts_with("Name=") {
                     name = line[5..].to_string();
                     remove_quotes(&mut name);
+                } else if comment == "" && line.starts_with("Comment=") {
+                    comment = line[8..].to_string();
+                    remove_quotes(&mut comment);
                 } else if app_type == "" && line.starts_with("Type=") {
                     app_type = line[5..].to_string();
                 } else if terminal == "" && line.starts_with("Terminal=") {
                     terminal = line[9..].to_string();
+                } else if wm_class == "" && line.starts_with("StartupWMClass=") {
+                    wm_class = line[15..].to_string();
+                    remove_quotes(&mut wm_class);
+                } else if single_main_window == "" && line.starts_with("SingleMainWindow=") {
+                    single_main_window = line[17..].to_string();
+                } else if mime_types == "" && line.starts_with("MimeType=") {
+                    mime_types = line[9..].to_string();
                 }
             }
 
@@ -229,10 +294,23 @@ fn scan_desktop_entries(
             }
             terminal.make_ascii_lowercase();
             let terminal = !(terminal == "" || terminal == "false");
+            let single_main_window = single_main_window.trim().to_lowercase() == "true";
+
+            let mime_types = mime_types
+                .split(';')
+                .map(|m| m.trim().to_owned())
+                .filter(|m| !m.is_empty())
+                .collect();
 
             apps.lock().unwrap().insert(name, App {
                 exec,
+                comment,
                 show_terminal: terminal,
+                wm_class,
+                single_main_window,
+                id: file_id + ".desktop",
+                mime_types,
+                exec_template,
             });
         }
     }
@@ -272,14 +350,41 @@ fn scan_path_dirs(apps: &Mutex<Apps>, progress: &Mutex<(u32, u32)>) {
                 let exec = path.to_string_lossy().into_owned();
 
                 apps.lock().unwrap().insert(name, App {
+                    exec_template: exec.clone(),
                     exec,
+                    comment: String::new(),
                     show_terminal: false,
+                    wm_class: String::new(),
+                    single_main_window: false,
+                    id: String::new(),
+                    mime_types: Vec::new(),
                 });
             }
         }
     }
 }
 
+/// Reads the names of all commands in the PATH, sorted and without duplicates
+pub fn read_commands() -> Vec<String> {
+    let mut commands = Vec::new();
+    if let Ok(path) = var("PATH") {
+        for dir in path.split(':') {
+            let files = match read_dir(dir) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            for file in files.filter_map(|f| f.ok()) {
+                if !file.path().is_dir() {
+                    commands.push(file.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    commands.sort();
+    commands.dedup();
+    commands
+}
+
 fn remove_quotes(string: &mut String) {
     // remove quotes if present
     if string.len() > 1 && string.starts_with('"') && string.ends_with('"') {
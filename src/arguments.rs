@@ -1,3 +1,7 @@
+use crate::search::SearchEngine;
+use std::env::args;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt, Clone)]
@@ -26,10 +30,30 @@ pub struct Args {
     #[structopt(long, default_value = "#242222", parse(try_from_str = parse_color))]
     pub color4: u64,
 
+    /// The color of the suggestion comments in the vertical layout
+    #[structopt(long, default_value = "#9e9e9e", parse(try_from_str = parse_color))]
+    pub color5: u64,
+
+    /// The color of the prompt text
+    #[structopt(long, default_value = "#ffffff", parse(try_from_str = parse_color))]
+    pub color6: u64,
+
+    /// The color of the prompt background
+    #[structopt(long, default_value = "#4f4c4c", parse(try_from_str = parse_color))]
+    pub color7: u64,
+
+    /// The color of the marked suggestions text
+    #[structopt(long, default_value = "#ffd75f", parse(try_from_str = parse_color))]
+    pub color8: u64,
+
     /// The height of the bar (in pixels)
     #[structopt(short, long, default_value = "22")]
     pub height: u32,
 
+    /// Show the suggestions vertically, in the given number of lines
+    #[structopt(short, long, default_value = "0")]
+    pub lines: u32,
+
     /// Show the bar on the bottom of the screen
     #[structopt(short, long)]
     pub bottom: bool,
@@ -42,12 +66,209 @@ pub struct Args {
     #[structopt(short, long, default_value = "i3-sensible-terminal")]
     pub terminal: String,
 
+    /// The browser to open bookmarks with (defaults to xdg-open)
+    #[structopt(long)]
+    pub browser: Option<String>,
+
     /// Scan the PATH variable.
     #[structopt(short, long)]
     pub path: bool,
+
+    /// Switch to the application's window if it's already running, instead of launching it again
+    #[structopt(short, long)]
+    pub raise: bool,
+
+    /// What to show suggestions for
+    #[structopt(short, long, default_value = "apps", possible_values = Mode::VARIANTS)]
+    pub mode: Mode,
+
+    /// The text shown before the input
+    #[structopt(long)]
+    pub prompt: Option<String>,
+
+    /// Allow marking several suggestions with Ctrl+Space. Enter then launches the marked
+    /// applications and prints the other marked suggestions, one per line
+    #[structopt(long)]
+    pub multi_select: bool,
+
+    /// Act as a GnuPG pinentry, talking the Assuan protocol on stdin/stdout
+    #[structopt(long)]
+    pub pinentry: bool,
+
+    /// The modes to switch between with Ctrl+Tab, separated by commas
+    #[structopt(long, use_delimiter = true, possible_values = Mode::VARIANTS)]
+    pub modes: Vec<Mode>,
+
+    /// The modes whose suggestions are shown together in the combi mode, separated by commas
+    #[structopt(long, default_value = "apps,windows", use_delimiter = true, possible_values = Mode::VARIANTS)]
+    pub combi_modes: Vec<Mode>,
+
+    /// List the applications that can open the file, with the default one first
+    #[structopt(long, parse(from_os_str))]
+    pub open_with: Option<PathBuf>,
+
+    /// The directory to start the file browser in (defaults to $HOME)
+    #[structopt(short, long, parse(from_os_str))]
+    pub directory: Option<PathBuf>,
+
+    /// Show hidden files in the file browser
+    #[structopt(long)]
+    pub hidden: bool,
+
+    /// A directory whose files are indexed for the locate mode (defaults to $HOME, can be given
+    /// multiple times)
+    #[structopt(long = "locate-root", number_of_values = 1, parse(from_os_str))]
+    pub locate_roots: Vec<PathBuf>,
+
+    /// A pattern of file and directory names to leave out of the locate index, like `*.o` or
+    /// `node_modules` (can be given multiple times)
+    #[structopt(long = "locate-ignore", number_of_values = 1)]
+    pub locate_ignore: Vec<String>,
+
+    /// The command that gives the entries in the script mode
+    #[structopt(short, long)]
+    pub script: Option<String>,
+
+    /// A command that gives results for the typed text as JSON lines (can be given multiple times)
+    #[structopt(long = "provider", number_of_values = 1)]
+    pub providers: Vec<String>,
+
+    /// How long to wait for the providers' results (in milliseconds)
+    #[structopt(long, default_value = "2000")]
+    pub provider_timeout: u64,
+
+    /// A search shortcut as keyword=URL, with {query} where the typed query goes (can be given
+    /// multiple times)
+    #[structopt(long = "search", number_of_values = 1)]
+    pub searches: Vec<SearchEngine>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mode {
+    /// Launch applications
+    Apps,
+    /// Switch to open windows
+    Windows,
+    /// Switch to workspaces or move the previously focused window to them
+    Workspaces,
+    /// Browse directories and open files
+    Files,
+    /// Connect to hosts over SSH
+    Ssh,
+    /// Show entries given by a script
+    Script,
+    /// Show the suggestions of several modes together
+    Combi,
+    /// Evaluate math expressions
+    Calc,
+    /// Run commands from the PATH
+    Run,
+    /// Read a password without showing it and print it to stdout
+    Password,
+    /// Pick emoji and other Unicode characters by name
+    Unicode,
+    /// Open the browsers' bookmarks and history
+    Bookmarks,
+    /// Run commands from the shells' history
+    History,
+    /// Open recently used files
+    Recent,
+    /// Terminate or kill the user's processes
+    Processes,
+    /// Open files found anywhere in the indexed directories
+    Locate,
+    /// Choose between buttons, only used by the pinentry
+    Confirm,
+    /// Choose the application to open a file with, only used with --open-with
+    OpenWith,
+}
+
+impl Mode {
+    const VARIANTS: &'static [&'static str] = &[
+        "apps",
+        "windows",
+        "workspaces",
+        "files",
+        "ssh",
+        "script",
+        "combi",
+        "calc",
+        "run",
+        "password",
+        "unicode",
+        "bookmarks",
+        "history",
+        "recent",
+        "processes",
+        "locate",
+    ];
+
+    /// The name of the mode, as it's given in the arguments
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Apps => "apps",
+            Mode::Windows => "windows",
+            Mode::Workspaces => "workspaces",
+            Mode::Files => "files",
+            Mode::Ssh => "ssh",
+            Mode::Script => "script",
+            Mode::Combi => "combi",
+            Mode::Calc => "calc",
+            Mode::Run => "run",
+            Mode::Password => "password",
+            Mode::Unicode => "unicode",
+            Mode::Bookmarks => "bookmarks",
+            Mode::History => "history",
+            Mode::Recent => "recent",
+            Mode::Processes => "processes",
+            Mode::Locate => "locate",
+            Mode::Confirm => "confirm",
+            Mode::OpenWith => "open-with",
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "apps" => Ok(Mode::Apps),
+            "windows" => Ok(Mode::Windows),
+            "workspaces" => Ok(Mode::Workspaces),
+            "files" => Ok(Mode::Files),
+            "ssh" => Ok(Mode::Ssh),
+            "script" => Ok(Mode::Script),
+            "combi" => Ok(Mode::Combi),
+            "calc" => Ok(Mode::Calc),
+            "run" => Ok(Mode::Run),
+            "password" => Ok(Mode::Password),
+            "unicode" => Ok(Mode::Unicode),
+            "bookmarks" => Ok(Mode::Bookmarks),
+            "history" => Ok(Mode::History),
+            "recent" => Ok(Mode::Recent),
+            "processes" => Ok(Mode::Processes),
+            "locate" => Ok(Mode::Locate),
+            _ => Err("Unknown mode"),
+        }
+    }
 }
 
 pub fn get_args() -> Args {
+    let arguments: Vec<String> = args().collect();
+    // gpg-agent can't be told to pass arguments, so a link named pinentry-* is used instead
+    let program = arguments
+        .first()
+        .and_then(|a| Path::new(a).file_name())
+        .unwrap_or_default();
+    if program.to_string_lossy().starts_with("pinentry") {
+        return Args::from_iter(&["rlaunch", "--pinentry"]);
+    }
+    // when used as SSH_ASKPASS or SUDO_ASKPASS, the only argument is the prompt
+    if arguments.len() == 2 && !arguments[1].starts_with('-') {
+        let prompt = arguments[1].trim().replace('\n', " ");
+        return Args::from_iter(&["rlaunch", "--mode", "password", "--prompt", &prompt]);
+    }
     Args::from_args()
 }
 
@@ -0,0 +1,493 @@
+use crate::recent::cache_dir;
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::env::var;
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, DirBuilder};
+use std::io::{Error, ErrorKind};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// How many of the most visited pages are read from every profile's history
+const MAX_HISTORY: u32 = 500;
+
+/// The directories of the Firefox-based browsers in $HOME, containing profiles.ini
+const FIREFOX_DIRS: &[(&str, &str)] = &[
+    ("Firefox", ".mozilla/firefox"),
+    ("Firefox", "snap/firefox/common/.mozilla/firefox"),
+    ("Firefox", ".var/app/org.mozilla.firefox/.mozilla/firefox"),
+    ("LibreWolf", ".librewolf"),
+    ("Waterfox", ".waterfox"),
+];
+
+/// The directories of the Chromium-based browsers in $XDG_CONFIG_HOME, containing a directory for every profile
+const CHROMIUM_DIRS: &[(&str, &str)] = &[
+    ("Chromium", "chromium"),
+    ("Chrome", "google-chrome"),
+    ("Chrome Beta", "google-chrome-beta"),
+    ("Chrome Dev", "google-chrome-unstable"),
+    ("Brave", "BraveSoftware/Brave-Browser"),
+    ("Vivaldi", "vivaldi"),
+    ("Edge", "microsoft-edge"),
+    ("Opera", "opera"),
+];
+
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// The browser it was found in and whether it's a bookmark or from the history
+    pub source: String,
+}
+
+/// Where bookmarks are read from
+#[derive(Debug, Clone)]
+pub enum Profile {
+    /// The directory of a Firefox profile, with places.sqlite
+    Firefox(&'static str, PathBuf),
+    /// The directory of a Chromium profile, with Bookmarks and History
+    Chromium(&'static str, PathBuf),
+}
+
+/// Reads the bookmarks and then the history of every browser profile found,
+/// without duplicates
+pub fn read_bookmarks() -> Vec<Bookmark> {
+    let profiles = find_profiles();
+    let mut bookmarks = Vec::new();
+    let mut history = Vec::new();
+    for profile in &profiles {
+        match profile {
+            Profile::Firefox(browser, dir) => {
+                let places = dir.join("places.sqlite");
+                bookmarks.extend(read_firefox_bookmarks(&places, browser));
+                history.extend(read_firefox_history(&places, browser));
+            }
+            Profile::Chromium(browser, dir) => {
+                bookmarks.extend(read_chromium_bookmarks(&dir.join("Bookmarks"), browser));
+                history.extend(read_chromium_history(&dir.join("History"), browser));
+            }
+        }
+    }
+    bookmarks.extend(history);
+
+    // the same page is often bookmarked in several browsers, or visited too
+    let mut seen = HashSet::new();
+    bookmarks.retain(|bookmark| seen.insert(bookmark.url.clone()));
+    bookmarks
+}
+
+/// Finds the profiles of the browsers in the standard directories
+pub fn find_profiles() -> Vec<Profile> {
+    let home = match var("HOME") {
+        Ok(home) => PathBuf::from(home),
+        Err(_) => return Vec::new(),
+    };
+    let config = match var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(".config"),
+    };
+
+    let mut profiles = Vec::new();
+    for (browser, dir) in FIREFOX_DIRS {
+        for profile in firefox_profiles(&home.join(dir)) {
+            profiles.push(Profile::Firefox(browser, profile));
+        }
+    }
+    for (browser, dir) in CHROMIUM_DIRS {
+        let entries = match read_dir(config.join(dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            // profiles are named "Default", "Profile 1" and so on, next to other directories
+            .filter(|path| path.join("Bookmarks").is_file() || path.join("History").is_file())
+            .collect();
+        dirs.sort();
+        for profile in dirs {
+            profiles.push(Profile::Chromium(browser, profile));
+        }
+    }
+    profiles
+}
+
+/// Reads the profile paths from profiles.ini
+fn firefox_profiles(dir: &Path) -> Vec<PathBuf> {
+    let contents = match read_to_string(dir.join("profiles.ini")) {
+        Ok(contents) => contents,
+        Err(_) => return Vec::new(),
+    };
+    // every profile has its own section, with the path relative to the directory or not
+    let mut sections: Vec<(Option<&str>, bool)> = Vec::new();
+    for line in contents.lines().map(|l| l.trim()) {
+        if line.starts_with('[') {
+            sections.push((None, true));
+        } else if let Some(section) = sections.last_mut() {
+            if let Some(path) = line.strip_prefix("Path=") {
+                section.0 = Some(path);
+            } else if let Some(relative) = line.strip_prefix("IsRelative=") {
+                section.1 = relative != "0";
+            }
+        }
+    }
+    let mut profiles = Vec::new();
+    for (path, relative) in sections {
+        let path = match path {
+            Some(path) if relative => dir.join(path),
+            Some(path) => PathBuf::from(path),
+            None => continue,
+        };
+        if path.join("places.sqlite").is_file() && !profiles.contains(&path) {
+            profiles.push(path);
+        }
+    }
+    profiles
+}
+
+/// Reads the bookmarks from Firefox's places.sqlite
+pub fn read_firefox_bookmarks(places: &Path, browser: &str) -> Vec<Bookmark> {
+    // type 1 is a bookmark, and place: URLs are saved searches, not pages
+    query_copy(
+        places,
+        "SELECT b.title, p.url FROM moz_bookmarks b JOIN moz_places p ON b.fk = p.id
+         WHERE b.type = 1 AND p.url NOT LIKE 'place:%' ORDER BY b.id",
+        &format!("{} bookmark", browser),
+    )
+}
+
+/// Reads the most frequently and recently visited pages from Firefox's places.sqlite
+pub fn read_firefox_history(places: &Path, browser: &str) -> Vec<Bookmark> {
+    query_copy(
+        places,
+        &format!(
+            "SELECT title, url FROM moz_places WHERE visit_count > 0 AND hidden = 0
+             ORDER BY frecency DESC LIMIT {}",
+            MAX_HISTORY
+        ),
+        &format!("{} history", browser),
+    )
+}
+
+/// Reads the most visited pages from Chromium's History
+pub fn read_chromium_history(history: &Path, browser: &str) -> Vec<Bookmark> {
+    query_copy(
+        history,
+        &format!(
+            "SELECT title, url FROM urls WHERE hidden = 0
+             ORDER BY visit_count DESC, last_visit_time DESC LIMIT {}",
+            MAX_HISTORY
+        ),
+        &format!("{} history", browser),
+    )
+}
+
+/// Reads the bookmarks from Chromium's Bookmarks JSON file
+pub fn read_chromium_bookmarks(path: &Path, browser: &str) -> Vec<Bookmark> {
+    let mut bookmarks = Vec::new();
+    let contents = match read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return bookmarks,
+    };
+    let json: Value = match serde_json::from_str(&contents) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Couldn't parse {:?}: {}", path, e);
+            return bookmarks;
+        }
+    };
+    // the bookmarks bar, other bookmarks and the mobile ones
+    if let Some(roots) = json["roots"].as_object() {
+        for root in roots.values() {
+            read_chromium_folder(root, &format!("{} bookmark", browser), &mut bookmarks);
+        }
+    }
+    bookmarks
+}
+
+fn read_chromium_folder(node: &Value, source: &str, bookmarks: &mut Vec<Bookmark>) {
+    match node["type"].as_str() {
+        Some("url") => {
+            if let Some(url) = node["url"].as_str() {
+                bookmarks.push(Bookmark {
+                    title: node["name"].as_str().unwrap_or("").to_owned(),
+                    url: url.to_owned(),
+                    source: source.to_owned(),
+                });
+            }
+        }
+        Some("folder") => {
+            for child in node["children"].as_array().into_iter().flatten() {
+                read_chromium_folder(child, source, bookmarks);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Runs the query, giving the title and URL, on a copy of the database.
+/// Browsers keep their databases locked while running, so they can't be read directly.
+fn query_copy(database: &Path, sql: &str, source: &str) -> Vec<Bookmark> {
+    if !database.is_file() {
+        return Vec::new();
+    }
+    let dir = match copy_database(database) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Couldn't copy {:?}: {}", database, e);
+            return Vec::new();
+        }
+    };
+    let result = query(&dir.join("database"), sql, source);
+    let _ = remove_dir_all(&dir);
+    match result {
+        Ok(bookmarks) => bookmarks,
+        Err(e) => {
+            eprintln!("Couldn't read {:?}: {}", database, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Copies the database with its write-ahead log, which has the latest changes,
+/// into a new directory that only the user can access, since the history is private
+fn copy_database(database: &Path) -> std::io::Result<PathBuf> {
+    let parent = match var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => cache_dir().ok_or_else(|| Error::new(ErrorKind::NotFound, "no cache directory"))?,
+    };
+    create_dir_all(&parent)?;
+    let name = database.file_name().unwrap_or_default().to_string_lossy();
+    let dir = parent.join(format!("rlaunch-{}-{}", process::id(), name));
+    // fails if the directory already exists, so that nothing planted there is used
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    copy(database, dir.join("database"))?;
+    let wal = database.with_file_name(format!("{}-wal", name));
+    if wal.is_file() {
+        copy(wal, dir.join("database-wal"))?;
+    }
+    Ok(dir)
+}
+
+fn query(database: &Path, sql: &str, source: &str) -> rusqlite::Result<Vec<Bookmark>> {
+    let connection = Connection::open_with_flags(
+        database,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut statement = connection.prepare(sql)?;
+    let rows = statement.query_map([], |row| {
+        Ok(Bookmark {
+            title: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+            url: row.get(1)?,
+            source: source.to_owned(),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Opens the URL with the given browser command, or the user's preferred application
+pub fn open_url(url: &str, browser: Option<&str>) {
+    let mut parts = match browser {
+        Some(browser) => crate::completion::split_command(browser),
+        None => vec!["xdg-open".to_owned()],
+    }
+    .into_iter();
+    if let Some(program) = parts.next() {
+        let _ = Command::new(program).args(parts).arg(url).spawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::write;
+
+    /// A new empty directory for the fixtures of a test
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("rlaunch-test-{}-{}", process::id(), name));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn pairs(bookmarks: &[Bookmark]) -> Vec<(&str, &str, &str)> {
+        bookmarks
+            .iter()
+            .map(|b| (b.title.as_str(), b.url.as_str(), b.source.as_str()))
+            .collect()
+    }
+
+    /// A places.sqlite with the tables and columns that are read
+    fn firefox_places(path: &Path) -> Connection {
+        let connection = Connection::open(path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT,
+                     visit_count INTEGER DEFAULT 0, hidden INTEGER DEFAULT 0, frecency INTEGER);
+                 CREATE TABLE moz_bookmarks (id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER,
+                     parent INTEGER, title TEXT);
+                 INSERT INTO moz_places VALUES
+                     (1, 'https://www.rust-lang.org/', 'Rust', 10, 0, 500),
+                     (2, 'https://crates.io/', 'crates.io', 3, 0, 900),
+                     (3, 'place:sort=8', NULL, 0, 0, 0),
+                     (4, 'https://hidden.example/', 'Hidden', 5, 1, 1000),
+                     (5, 'https://never.example/', 'Never visited', 0, 0, 0);
+                 INSERT INTO moz_bookmarks VALUES
+                     (1, 2, NULL, 0, 'toolbar'),
+                     (2, 1, 1, 1, 'The Rust language'),
+                     (3, 1, 3, 1, 'Most visited'),
+                     (4, 1, 5, 1, NULL);",
+            )
+            .unwrap();
+        connection
+    }
+
+    #[test]
+    fn firefox() {
+        let dir = fixture_dir("firefox");
+        let places = dir.join("places.sqlite");
+        drop(firefox_places(&places));
+        assert_eq!(
+            pairs(&read_firefox_bookmarks(&places, "Firefox")),
+            vec![
+                (
+                    "The Rust language",
+                    "https://www.rust-lang.org/",
+                    "Firefox bookmark"
+                ),
+                ("", "https://never.example/", "Firefox bookmark"),
+            ]
+        );
+        // the most frecent first, without hidden and unvisited pages
+        assert_eq!(
+            pairs(&read_firefox_history(&places, "Firefox")),
+            vec![
+                ("crates.io", "https://crates.io/", "Firefox history"),
+                ("Rust", "https://www.rust-lang.org/", "Firefox history"),
+            ]
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn firefox_write_ahead_log() {
+        let dir = fixture_dir("firefox-wal");
+        let places = dir.join("places.sqlite");
+        // like a running browser, with changes that are only in places.sqlite-wal
+        let connection = firefox_places(&places);
+        connection
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .unwrap();
+        connection
+            .execute_batch(
+                "PRAGMA wal_autocheckpoint = 0;
+                 INSERT INTO moz_places VALUES (6, 'https://new.example/', 'New', 1, 0, 2000);",
+            )
+            .unwrap();
+        assert!(dir.join("places.sqlite-wal").is_file());
+        let history = read_firefox_history(&places, "Firefox");
+        assert_eq!(history[0].url, "https://new.example/");
+        drop(connection);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn firefox_profiles_ini() {
+        let dir = fixture_dir("profiles");
+        for profile in &["abc.default-release", "xyz.other"] {
+            create_dir_all(dir.join(profile)).unwrap();
+            write(dir.join(profile).join("places.sqlite"), "").unwrap();
+        }
+        let absolute = dir.join("xyz.other");
+        write(
+            dir.join("profiles.ini"),
+            format!(
+                "[Install4F96D1932A9F858E]\nDefault=abc.default-release\n\n\
+                 [Profile1]\nName=other\nIsRelative=0\nPath={}\n\n\
+                 [Profile0]\nName=default-release\nIsRelative=1\nPath=abc.default-release\n\n\
+                 [Profile2]\nName=missing\nIsRelative=1\nPath=missing\n\n\
+                 [General]\nVersion=2\n",
+                absolute.display()
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            firefox_profiles(&dir),
+            vec![absolute, dir.join("abc.default-release")]
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chromium() {
+        let dir = fixture_dir("chromium");
+        let history = dir.join("History");
+        Connection::open(&history)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT, title TEXT,
+                     visit_count INTEGER, last_visit_time INTEGER, hidden INTEGER);
+                 INSERT INTO urls VALUES
+                     (1, 'https://docs.rs/', 'Docs.rs', 4, 100, 0),
+                     (2, 'https://github.com/', 'GitHub', 9, 50, 0),
+                     (3, 'https://example.com/', 'Example', 4, 200, 0),
+                     (4, 'https://hidden.example/', 'Hidden', 99, 300, 1);",
+            )
+            .unwrap();
+        assert_eq!(
+            pairs(&read_chromium_history(&history, "Brave")),
+            vec![
+                ("GitHub", "https://github.com/", "Brave history"),
+                ("Example", "https://example.com/", "Brave history"),
+                ("Docs.rs", "https://docs.rs/", "Brave history"),
+            ]
+        );
+
+        let bookmarks = dir.join("Bookmarks");
+        write(
+            &bookmarks,
+            r#"{
+                "checksum": "0",
+                "roots": {
+                    "bookmark_bar": {"type": "folder", "name": "Bookmarks bar", "children": [
+                        {"type": "url", "name": "Rust", "url": "https://www.rust-lang.org/"},
+                        {"type": "folder", "name": "Nested", "children": [
+                            {"type": "url", "name": "crates.io", "url": "https://crates.io/"}
+                        ]}
+                    ]},
+                    "other": {"type": "folder", "name": "Other", "children": [
+                        {"type": "url", "name": "No URL"}
+                    ]}
+                },
+                "version": 1
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            pairs(&read_chromium_bookmarks(&bookmarks, "Brave")),
+            vec![
+                ("Rust", "https://www.rust-lang.org/", "Brave bookmark"),
+                ("crates.io", "https://crates.io/", "Brave bookmark"),
+            ]
+        );
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn private_copy() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = fixture_dir("copy");
+        let database = dir.join("History");
+        write(&database, "data").unwrap();
+        let copied = copy_database(&database).unwrap();
+        let mode = copied.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert_eq!(read_to_string(copied.join("database")).unwrap(), "data");
+        // a directory that's already there isn't used
+        assert!(copy_database(&database).is_err());
+        remove_dir_all(&copied).unwrap();
+        remove_dir_all(&dir).unwrap();
+    }
+}
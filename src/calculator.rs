@@ -0,0 +1,530 @@
+use std::f64::consts::{E, PI};
+
+/// Evaluates the query as a math expression, optionally converting between units
+/// (`5 MiB in KB`, `70 F to C`, `255 in hex`).
+///
+/// Returns None if the query is not an expression, or if it's just a plain number or constant,
+/// so that typing application names doesn't show results.
+pub fn evaluate(query: &str) -> Option<String> {
+    let query = query.trim();
+    if let Some((value, to)) = split_conversion(query) {
+        return convert(value, to);
+    }
+
+    let mut parser = Parser::new(query)?;
+    let value = parser.parse()?;
+    if parser.trivial {
+        return None;
+    }
+    Some(format_number(value))
+}
+
+/// Splits `<value> in <unit>` or `<value> to <unit>` at the last such keyword
+fn split_conversion(query: &str) -> Option<(&str, &str)> {
+    let position = match (query.rfind(" in "), query.rfind(" to ")) {
+        (Some(a), Some(b)) => a.max(b),
+        (a, b) => a.or(b)?,
+    };
+    let (value, to) = (query[..position].trim(), query[position + 4..].trim());
+    if value.is_empty() || to.is_empty() {
+        return None;
+    }
+    Some((value, to))
+}
+
+fn convert(value: &str, to: &str) -> Option<String> {
+    // the letters of a number in another base, like 0xff, aren't a unit
+    if let Some(converted) = convert_base(value, to) {
+        return Some(converted);
+    }
+    // the unit of the value is the trailing word of it, if there is one
+    let unit_start = value
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphabetic() || *c == '°')
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| value.len());
+    let (expression, from) = (&value[..unit_start], &value[unit_start..]);
+    let from_unit = find_unit(from)?;
+    let to_unit = find_unit(to)?;
+    if from_unit.kind != to_unit.kind {
+        return None;
+    }
+    let value = Parser::new(expression)?.parse()?;
+    let converted = (value * from_unit.factor + from_unit.offset - to_unit.offset) / to_unit.factor;
+    Some(format!("{} {}", format_number(converted), to_unit.names[0]))
+}
+
+/// Converts a plain number to another base
+fn convert_base(expression: &str, to: &str) -> Option<String> {
+    let value = Parser::new(expression)?.parse()?;
+    if value.fract() != 0.0 || value.abs() > i64::MAX as f64 {
+        return None;
+    }
+    let value = value as i64;
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.unsigned_abs();
+    match to.to_lowercase().as_str() {
+        "hex" | "hexadecimal" => Some(format!("{}0x{:x}", sign, value)),
+        "bin" | "binary" => Some(format!("{}0b{:b}", sign, value)),
+        "oct" | "octal" => Some(format!("{}0o{:o}", sign, value)),
+        "dec" | "decimal" => Some(format!("{}{}", sign, value)),
+        _ => None,
+    }
+}
+
+#[derive(PartialEq)]
+enum UnitKind {
+    Data,
+    Length,
+    Mass,
+    Time,
+    Temperature,
+    Volume,
+}
+
+/// A value in this unit equals `value * factor + offset` in the base unit of its kind
+struct Unit {
+    names: &'static [&'static str],
+    kind: UnitKind,
+    factor: f64,
+    offset: f64,
+}
+
+const fn unit(names: &'static [&'static str], kind: UnitKind, factor: f64) -> Unit {
+    Unit {
+        names,
+        kind,
+        factor,
+        offset: 0.0,
+    }
+}
+
+const UNITS: &[Unit] = &[
+    unit(&["B", "byte", "bytes"], UnitKind::Data, 1.0),
+    unit(&["bit", "bits"], UnitKind::Data, 0.125),
+    unit(&["KB", "kB"], UnitKind::Data, 1e3),
+    unit(&["MB"], UnitKind::Data, 1e6),
+    unit(&["GB"], UnitKind::Data, 1e9),
+    unit(&["TB"], UnitKind::Data, 1e12),
+    unit(&["PB"], UnitKind::Data, 1e15),
+    unit(&["KiB"], UnitKind::Data, 1024.0),
+    unit(&["MiB"], UnitKind::Data, 1_048_576.0),
+    unit(&["GiB"], UnitKind::Data, 1_073_741_824.0),
+    unit(&["TiB"], UnitKind::Data, 1_099_511_627_776.0),
+    unit(&["PiB"], UnitKind::Data, 1_125_899_906_842_624.0),
+    unit(&["mm"], UnitKind::Length, 1e-3),
+    unit(&["cm"], UnitKind::Length, 1e-2),
+    unit(&["m"], UnitKind::Length, 1.0),
+    unit(&["km"], UnitKind::Length, 1e3),
+    unit(&["in", "inch", "inches"], UnitKind::Length, 0.0254),
+    unit(&["ft", "feet", "foot"], UnitKind::Length, 0.3048),
+    unit(&["yd", "yard", "yards"], UnitKind::Length, 0.9144),
+    unit(&["mi", "mile", "miles"], UnitKind::Length, 1609.344),
+    unit(&["mg"], UnitKind::Mass, 1e-3),
+    unit(&["g"], UnitKind::Mass, 1.0),
+    unit(&["kg"], UnitKind::Mass, 1e3),
+    unit(&["t"], UnitKind::Mass, 1e6),
+    unit(&["oz"], UnitKind::Mass, 28.349_523_125),
+    unit(&["lb", "lbs"], UnitKind::Mass, 453.592_37),
+    unit(&["ms"], UnitKind::Time, 1e-3),
+    unit(&["s", "sec"], UnitKind::Time, 1.0),
+    unit(&["min"], UnitKind::Time, 60.0),
+    unit(&["h", "hr", "hours"], UnitKind::Time, 3600.0),
+    unit(&["d", "day", "days"], UnitKind::Time, 86400.0),
+    unit(&["week", "weeks"], UnitKind::Time, 604_800.0),
+    unit(&["ml", "mL"], UnitKind::Volume, 1e-3),
+    unit(&["l", "L"], UnitKind::Volume, 1.0),
+    unit(&["gal"], UnitKind::Volume, 3.785_411_784),
+    unit(&["K"], UnitKind::Temperature, 1.0),
+    Unit {
+        names: &["C", "°C"],
+        kind: UnitKind::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        names: &["F", "°F"],
+        kind: UnitKind::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+];
+
+/// Finds a unit by its exact name, falling back to a case-insensitive match
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    UNITS
+        .iter()
+        .find(|unit| unit.names.contains(&name))
+        .or_else(|| {
+            UNITS.iter().find(|unit| {
+                unit.names
+                    .iter()
+                    .any(|unit_name| unit_name.eq_ignore_ascii_case(name))
+            })
+        })
+}
+
+/// Formats the number without a fractional part if it's whole, and with at most 10 decimals otherwise
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    let formatted = format!("{:.10}", value);
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned()
+    } else {
+        formatted
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            // hexadecimal, binary and octal literals
+            let radix = match (c, chars.get(i + 1)) {
+                ('0', Some('x')) | ('0', Some('X')) => 16,
+                ('0', Some('b')) | ('0', Some('B')) => 2,
+                ('0', Some('o')) | ('0', Some('O')) => 8,
+                _ => 10,
+            };
+            if radix != 10 {
+                i += 2;
+                let digits_start = i;
+                while i < chars.len() && chars[i].is_digit(radix) {
+                    i += 1;
+                }
+                let digits: String = chars[digits_start..i].iter().collect();
+                let number = u64::from_str_radix(&digits, radix).ok()?;
+                tokens.push(Token::Number(number as f64));
+                continue;
+            }
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // scientific notation, but only if an exponent follows, since `e` is also a constant
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(number.parse().ok()?));
+        } else if c.is_alphabetic() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            tokens.push(Token::Op('^'));
+            i += 2;
+        } else {
+            tokens.push(match c {
+                '+' | '-' | '*' | '/' | '%' | '^' => Token::Op(c),
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                _ => return None,
+            });
+            i += 1;
+        }
+    }
+    Some(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// Whether the expression is just a number or a constant so far
+    trivial: bool,
+}
+
+impl Parser {
+    fn new(text: &str) -> Option<Self> {
+        let tokens = tokenize(text)?;
+        // hex, binary and octal literals on their own are worth converting to decimal
+        let trivial = !text.trim().starts_with("0x")
+            && !text.trim().starts_with("0b")
+            && !text.trim().starts_with("0o");
+        Some(Self {
+            tokens,
+            position: 0,
+            trivial,
+        })
+    }
+    fn parse(&mut self) -> Option<f64> {
+        let value = self.expression()?;
+        if self.position != self.tokens.len() || !value.is_finite() {
+            return None;
+        }
+        Some(value)
+    }
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if op != '+' && op != '-' {
+                break;
+            }
+            self.position += 1;
+            self.trivial = false;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if op != '*' && op != '/' && op != '%' {
+                break;
+            }
+            self.position += 1;
+            self.trivial = false;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Some(value)
+    }
+    fn unary(&mut self) -> Option<f64> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.position += 1;
+                Some(-self.unary()?)
+            }
+            Some(Token::Op('+')) => {
+                self.position += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+    fn power(&mut self) -> Option<f64> {
+        let base = self.primary()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.position += 1;
+            self.trivial = false;
+            // right associative, so that 2^3^2 is 2^9
+            let exponent = self.unary()?;
+            return Some(base.powf(exponent));
+        }
+        Some(base)
+    }
+    fn primary(&mut self) -> Option<f64> {
+        match self.next()? {
+            Token::Number(n) => Some(n),
+            Token::LeftParen => {
+                let value = self.expression()?;
+                if self.next()? != Token::RightParen {
+                    return None;
+                }
+                Some(value)
+            }
+            Token::Ident(name) => {
+                if let Some(Token::LeftParen) = self.peek() {
+                    self.position += 1;
+                    self.trivial = false;
+                    let argument = self.expression()?;
+                    if self.next()? != Token::RightParen {
+                        return None;
+                    }
+                    call_function(&name, argument)
+                } else {
+                    match name.as_str() {
+                        "pi" => Some(PI),
+                        "e" => Some(E),
+                        _ => None,
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn call_function(name: &str, x: f64) -> Option<f64> {
+    Some(match name {
+        "sqrt" => x.sqrt(),
+        "cbrt" => x.cbrt(),
+        "abs" => x.abs(),
+        "floor" => x.floor(),
+        "ceil" => x.ceil(),
+        "round" => x.round(),
+        "exp" => x.exp(),
+        "ln" => x.ln(),
+        "log" | "log10" => x.log10(),
+        "log2" => x.log2(),
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+
+    fn check(cases: &[(&str, Option<&str>)]) {
+        for (query, expected) in cases {
+            assert_eq!(evaluate(query).as_deref(), *expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        check(&[
+            ("1 + 2 * 3", Some("7")),
+            ("(1 + 2) * 3", Some("9")),
+            ("10 - 4 - 3", Some("3")),
+            ("12 / 3 / 2", Some("2")),
+            ("2 ^ 3 ^ 2", Some("512")),
+            ("2 ** 10", Some("1024")),
+            ("7 % 4 + 1", Some("4")),
+            ("1 / 3", Some("0.3333333333")),
+        ]);
+    }
+
+    #[test]
+    fn unary_minus() {
+        check(&[
+            ("-3 + 5", Some("2")),
+            ("-2 ^ 2", Some("-4")),
+            ("2 ^ -1", Some("0.5")),
+            ("3 - -2", Some("5")),
+            ("-(1 + 2)", Some("-3")),
+            ("+4 * 2", Some("8")),
+        ]);
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        check(&[
+            ("sqrt(16)", Some("4")),
+            ("abs(-2.5)", Some("2.5")),
+            ("log(1000)", Some("3")),
+            ("floor(2.7) + ceil(2.2)", Some("5")),
+            ("2 * pi", Some("6.2831853072")),
+            ("cos(0)", Some("1")),
+            ("nosuch(1)", None),
+        ]);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        check(&[("1 / 0", None), ("0 / 0", None), ("5 % 0", None)]);
+    }
+
+    #[test]
+    fn malformed_input() {
+        check(&[
+            ("", None),
+            ("1 +", None),
+            ("(1 + 2", None),
+            ("1 + 2)", None),
+            ("* 3", None),
+            ("2 $ 3", None),
+            ("firefox", None),
+            ("sqrt 4", None),
+        ]);
+    }
+
+    #[test]
+    fn unit_conversions() {
+        check(&[
+            ("5 MiB in KB", Some("5242.88 KB")),
+            ("1 GB to MiB", Some("953.6743164062 MiB")),
+            ("8 bits in B", Some("1 B")),
+            ("70 F in C", Some("21.1111111111 C")),
+            ("100 C to F", Some("212 F")),
+            ("-40 °C in °F", Some("-40 F")),
+            ("0 K in C", Some("-273.15 C")),
+            ("1 mile in km", Some("1.609344 km")),
+            ("12 inches to cm", Some("30.48 cm")),
+            ("3 ft in m", Some("0.9144 m")),
+            ("1 kg in lb", Some("2.2046226218 lb")),
+            ("16 oz to g", Some("453.59237 g")),
+            ("2 * 1.5 h in min", Some("180 min")),
+            // the case only matters when it tells units apart
+            ("1 gib in mib", Some("1024 MiB")),
+        ]);
+    }
+
+    #[test]
+    fn mismatched_units() {
+        check(&[
+            ("5 kg in m", None),
+            ("1 h to C", None),
+            ("5 parsecs in m", None),
+            ("5 kg in", None),
+            ("in m", None),
+        ]);
+    }
+
+    #[test]
+    fn bases() {
+        check(&[
+            ("255 in hex", Some("0xff")),
+            ("-5 in bin", Some("-0b101")),
+            ("8 to oct", Some("0o10")),
+            ("0xff in dec", Some("255")),
+            ("0b1010 + 1 to hexadecimal", Some("0xb")),
+            ("0x1f in oct", Some("0o37")),
+            // only whole numbers have digits in other bases
+            ("1.5 in hex", None),
+            ("255 in base64", None),
+        ]);
+    }
+
+    #[test]
+    fn trivial_input() {
+        // plain numbers and constants aren't results, but other bases are converted
+        check(&[
+            ("42", None),
+            ("pi", None),
+            ("0xff", Some("255")),
+            ("0b101", Some("5")),
+        ]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::env::var;
+use std::fs::read_dir;
+
+/// Shell-style completion of the path-like word before the caret
+pub struct PathCompletion {
+    /// The byte index where the completed word starts in the text
+    pub start: usize,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl PathCompletion {
+    /// Finds the completions for the word that ends at the caret,
+    /// if it looks like a path (starts with `/`, `~/`, `./` or `../`)
+    pub fn new(text: &str, caret: usize) -> Option<Self> {
+        let start = word_start(&text[..caret]);
+        let word = &text[start..caret];
+        if !["/", "~/", "./", "../", "$"]
+            .iter()
+            .any(|prefix| word.starts_with(prefix))
+        {
+            return None;
+        }
+
+        // keep the directory part as it was typed and only complete the last component
+        let (typed_dir, typed_prefix) = match word.rfind('/') {
+            Some(i) => word.split_at(i + 1),
+            None => return None,
+        };
+        let dir = expand(&unescape(typed_dir));
+        let prefix = unescape(typed_prefix);
+
+        let mut candidates: Vec<String> = read_dir(&dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // hidden files only if asked for explicitly
+                if !name.starts_with(&prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                let suffix = if entry.path().is_dir() { "/" } else { "" };
+                Some(format!("{}{}{}", typed_dir, escape(&name), suffix))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        candidates.sort();
+
+        Some(Self {
+            start,
+            candidates,
+            index: 0,
+        })
+    }
+    pub fn current(&self) -> &str {
+        &self.candidates[self.index]
+    }
+    /// Cycles to the next completion, wrapping around at the end
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.candidates.len();
+    }
+}
+
+/// Returns the index where the last word starts, not counting escaped spaces as separators
+fn word_start(text: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ' ' {
+            start = i + 1;
+        }
+    }
+    start
+}
+
+/// Expands `~` and environment variables (`$VAR` or `${VAR}`)
+pub fn expand(path: &str) -> String {
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            var("HOME").unwrap_or_default() + rest
+        }
+        _ => path.to_owned(),
+    };
+
+    let mut expanded = String::new();
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            for c in &mut chars {
+                if c == '}' {
+                    break;
+                }
+                name.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+        expanded += &var(name).unwrap_or_default();
+    }
+    expanded
+}
+
+/// Escapes characters that would otherwise split the word or be interpreted specially
+pub fn escape(word: &str) -> String {
+    let mut escaped = String::new();
+    for c in word.chars() {
+        if c.is_whitespace() || "\\'\"$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(word: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                unescaped.push(c);
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// Splits the command into arguments by spaces, honoring quotes and backslash escapes
+/// and expanding `~` and environment variables like a shell would
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('~', None) if !in_argument && matches!(chars.peek(), None | Some('/') | Some(' ')) => {
+                current += &var("HOME").unwrap_or_default();
+                in_argument = true;
+            }
+            ('$', q) if q != Some('\'') => {
+                let mut name = String::from("$");
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '{' || c == '}') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                    if c == '}' {
+                        break;
+                    }
+                }
+                if name == "$" {
+                    current.push('$');
+                } else {
+                    current += &expand(&name);
+                }
+                in_argument = true;
+            }
+            ('\\', q) if q != Some('\'') => {
+                if let Some(c) = chars.next() {
+                    current.push(c);
+                }
+                in_argument = true;
+            }
+            ('"', None) | ('\'', None) => {
+                quote = Some(c);
+                in_argument = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (' ', None) => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            _ => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+    if in_argument {
+        arguments.push(current);
+    }
+    arguments
+}
@@ -0,0 +1,98 @@
+use std::cmp::Reverse;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub modified: SystemTime,
+}
+
+/// The state of the file browser mode
+pub struct FileBrowser {
+    pub directory: PathBuf,
+    pub entries: Vec<FileEntry>,
+    show_hidden: bool,
+    sort_by_mtime: bool,
+}
+
+impl FileBrowser {
+    pub fn new(directory: PathBuf, show_hidden: bool) -> Self {
+        let mut browser = Self {
+            directory,
+            entries: Vec::new(),
+            show_hidden,
+            sort_by_mtime: false,
+        };
+        browser.read();
+        browser
+    }
+    /// Descends into the given subdirectory
+    pub fn enter(&mut self, name: &str) {
+        self.directory.push(name);
+        self.read();
+    }
+    /// Goes up one level
+    pub fn up(&mut self) {
+        if self.directory.pop() {
+            self.read();
+        }
+    }
+    pub fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+        self.read();
+    }
+    /// Switches between sorting by name and by modification time (newest first)
+    pub fn toggle_sort(&mut self) {
+        self.sort_by_mtime = !self.sort_by_mtime;
+        self.sort();
+    }
+    fn read(&mut self) {
+        self.entries.clear();
+        let files = match read_dir(&self.directory) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Couldn't read {:?}: {}", self.directory, e);
+                return;
+            }
+        };
+        for file in files {
+            let file = match file {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            let name = file.file_name().to_string_lossy().into_owned();
+            if !self.show_hidden && name.starts_with('.') {
+                continue;
+            }
+            // follow symlinks, so that links to directories can be entered
+            let metadata = match file.path().metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            self.entries.push(FileEntry {
+                name,
+                is_dir: metadata.is_dir(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+        self.sort();
+    }
+    fn sort(&mut self) {
+        if self.sort_by_mtime {
+            self.entries.sort_by_key(|e| Reverse(e.modified));
+        } else {
+            // directories first
+            self.entries
+                .sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+        }
+    }
+}
+
+/// Opens the file with the user's preferred application
+pub fn open_file(path: &Path) {
+    let _ = Command::new("xdg-open").arg(path).spawn();
+}
@@ -0,0 +1,257 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env::var;
+use std::fs::{metadata, read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+/// How many of the most recent commands are shown
+const MAX_COMMANDS: usize = 10000;
+
+/// The commands read from a history file, with the times they were run at if they're known
+type Commands = Vec<(String, Option<u64>)>;
+
+/// A command from a history file
+struct Entry {
+    command: String,
+    /// When it was run, in seconds since the epoch
+    time: u64,
+    /// The position in its file, newer commands come later
+    index: usize,
+}
+
+/// Reads the commands from the bash, zsh and fish histories, the most recent first and
+/// without duplicates
+pub fn read_history() -> Vec<String> {
+    let home = match var("HOME") {
+        Ok(home) => PathBuf::from(home),
+        Err(_) => return Vec::new(),
+    };
+    let zsh_dir = match var("ZDOTDIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.clone(),
+    };
+    let data_dir = match var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(".local/share"),
+    };
+
+    let mut entries = Vec::new();
+    read_file(&home.join(".bash_history"), read_bash, &mut entries);
+    read_file(&zsh_dir.join(".zsh_history"), read_zsh, &mut entries);
+    read_file(&data_dir.join("fish/fish_history"), read_fish, &mut entries);
+
+    entries.sort_by_key(|entry| Reverse((entry.time, entry.index)));
+    let mut seen = HashSet::new();
+    entries
+        .into_iter()
+        .map(|entry| entry.command)
+        .filter(|command| !command.trim().is_empty() && seen.insert(command.clone()))
+        .take(MAX_COMMANDS)
+        .collect()
+}
+
+/// Reads the history with the parser. The commands without a known time get the time the file
+/// was modified at.
+fn read_file(path: &Path, parse: fn(&[u8]) -> Commands, entries: &mut Vec<Entry>) {
+    let contents = match read(path) {
+        Ok(contents) => contents,
+        Err(_) => return,
+    };
+    let modified = metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    for (index, (command, time)) in parse(&contents).into_iter().enumerate() {
+        entries.push(Entry {
+            command,
+            time: time.unwrap_or(modified),
+            index,
+        });
+    }
+}
+
+/// One command per line, preceded by a `#<timestamp>` line if HISTTIMEFORMAT was set
+fn read_bash(contents: &[u8]) -> Commands {
+    let mut commands = Vec::new();
+    let mut time = None;
+    for line in String::from_utf8_lossy(contents).lines() {
+        if let Some(timestamp) = line.strip_prefix('#').and_then(|t| t.parse().ok()) {
+            time = Some(timestamp);
+        } else {
+            commands.push((line.to_owned(), time.take()));
+        }
+    }
+    commands
+}
+
+/// One command per line, or `: <start>:<duration>;<command>` with EXTENDED_HISTORY.
+/// Lines of multi-line commands end with a backslash.
+fn read_zsh(contents: &[u8]) -> Commands {
+    let contents = unmetafy(contents);
+    let mut commands: Commands = Vec::new();
+    let mut continued = false;
+    for line in String::from_utf8_lossy(&contents).lines() {
+        if continued {
+            if let Some((command, _)) = commands.last_mut() {
+                command.push('\n');
+                command.push_str(line.strip_suffix('\\').unwrap_or(line));
+            }
+            continued = line.ends_with('\\');
+            continue;
+        }
+        continued = line.ends_with('\\');
+        let line = line.strip_suffix('\\').unwrap_or(line);
+        let extended = line
+            .strip_prefix(": ")
+            .and_then(|rest| rest.find(';').map(|i| (&rest[..i], &rest[i + 1..])));
+        match extended {
+            Some((times, command)) => {
+                let time = times.split(':').next().and_then(|t| t.parse().ok());
+                commands.push((command.to_owned(), time));
+            }
+            None => commands.push((line.to_owned(), None)),
+        }
+    }
+    commands
+}
+
+/// zsh writes the bytes 0x83 to 0x9f and some others as 0x83 followed by the byte xored with 32
+fn unmetafy(contents: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(contents.len());
+    let mut iter = contents.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == 0x83 {
+            if let Some(&next) = iter.next() {
+                bytes.push(next ^ 32);
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+/// A YAML-like list of `- cmd: <command>` followed by `  when: <timestamp>` and other fields
+fn read_fish(contents: &[u8]) -> Commands {
+    let mut commands: Commands = Vec::new();
+    for line in String::from_utf8_lossy(contents).lines() {
+        if let Some(command) = line.strip_prefix("- cmd: ") {
+            commands.push((unescape_fish(command), None));
+        } else if let Some(time) = line.strip_prefix("  when: ") {
+            if let Some((_, when)) = commands.last_mut() {
+                *when = time.trim().parse().ok();
+            }
+        }
+    }
+    commands
+}
+
+/// Newlines are written as `\n` and backslashes as `\\`
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Runs the command with the user's shell, in the terminal if one is given.
+/// The terminal is kept open with a shell after the command finishes, to see its output.
+pub fn run_in_shell(command: &str, terminal: Option<&[String]>) {
+    let shell = var("SHELL").unwrap_or_else(|_| "/bin/sh".to_owned());
+    let mut c = match terminal {
+        Some([program, arguments @ ..]) => {
+            let mut c = Command::new(program);
+            c.args(arguments)
+                .arg("-e")
+                .arg(&shell)
+                .arg("-c")
+                .arg(format!("{}\nexec {}", command, shell));
+            c
+        }
+        _ => {
+            let mut c = Command::new(&shell);
+            c.arg("-c").arg(command);
+            c
+        }
+    };
+    let _ = c.spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(commands: &[(&str, Option<u64>)]) -> Commands {
+        commands
+            .iter()
+            .map(|(command, time)| ((*command).to_owned(), *time))
+            .collect()
+    }
+
+    #[test]
+    fn bash() {
+        let history =
+            b"ls -l\n#1700000000\ncd /tmp\n#1700000005\ngit status\n# a comment\necho hi\n";
+        assert_eq!(
+            read_bash(history),
+            commands(&[
+                ("ls -l", None),
+                ("cd /tmp", Some(1700000000)),
+                ("git status", Some(1700000005)),
+                // only a number makes a timestamp
+                ("# a comment", None),
+                ("echo hi", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn zsh() {
+        // "→" is e2 86 92, whose last two bytes zsh metafies
+        let history = b": 1700000000:0;ls\n\
+            : 1700000010:3;echo \xe2\x83\xa6\x83\xb2\n\
+            : 1700000020:0;for i in a b\\\n\
+            do echo $i\\\n\
+            done\n\
+            plain command\n\
+            : not;extended\n";
+        assert_eq!(
+            read_zsh(history),
+            commands(&[
+                ("ls", Some(1700000000)),
+                ("echo →", Some(1700000010)),
+                ("for i in a b\ndo echo $i\ndone", Some(1700000020)),
+                ("plain command", None),
+                ("extended", None),
+            ])
+        );
+    }
+
+    #[test]
+    fn fish() {
+        let history = b"- cmd: ls\n  when: 1700000000\n\
+            - cmd: echo 'a\\nb' \\\\n\n  when: 1700000005\n  paths:\n    - /tmp\n\
+            - cmd: no time\n";
+        assert_eq!(
+            read_fish(history),
+            commands(&[
+                ("ls", Some(1700000000)),
+                ("echo 'a\nb' \\n", Some(1700000005)),
+                ("no time", None),
+            ])
+        );
+    }
+}
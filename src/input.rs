@@ -0,0 +1,157 @@
+use crate::completion::{expand, split_command};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// What the typed text is, when it's not a command
+#[derive(Debug, Clone)]
+pub enum Input {
+    Url(String),
+    /// An existing file or directory
+    Path(PathBuf),
+    /// An existing executable file, which is run rather than opened
+    Executable(PathBuf),
+    /// A mailto: URL
+    Mail(String),
+}
+
+/// Recognizes URLs, paths of existing files and email addresses
+pub fn classify(text: &str) -> Option<Input> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if let Some(path) = existing_path(text) {
+        let executable = path
+            .metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+        if executable {
+            return Some(Input::Executable(path));
+        }
+        return Some(Input::Path(path));
+    }
+    // the rest can't contain spaces
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    if text.starts_with("mailto:") {
+        return Some(Input::Mail(text.to_owned()));
+    }
+    if is_email(text) {
+        return Some(Input::Mail(format!("mailto:{}", text)));
+    }
+    if has_scheme(text) {
+        return Some(Input::Url(text.to_owned()));
+    }
+    // without a scheme only something like example.com/... or www.example.com is a URL,
+    // a bare example.com could be a file or command name too
+    let host_end = text.find('/').unwrap_or(text.len());
+    let host = &text[..host_end];
+    if is_domain(host) && (host_end < text.len() || host.starts_with("www.")) {
+        return Some(Input::Url(format!("https://{}", text)));
+    }
+    None
+}
+
+/// Paths have to start like a path, since rlaunch could be started in any directory
+fn existing_path(text: &str) -> Option<PathBuf> {
+    if !["/", "~", "./", "../", "$"]
+        .iter()
+        .any(|prefix| text.starts_with(prefix))
+    {
+        return None;
+    }
+    // paths completed with Tab have their spaces escaped
+    let arguments = split_command(text);
+    let path = match arguments.as_slice() {
+        [path] => PathBuf::from(path),
+        _ => PathBuf::from(expand(text)),
+    };
+    if path.exists() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Like https://, the scheme is letters, digits, `+`, `.` and `-`, starting with a letter
+fn has_scheme(text: &str) -> bool {
+    match text.find("://") {
+        Some(i) if i > 0 => {
+            let scheme = &text[..i];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        }
+        _ => false,
+    }
+}
+
+/// A name with at least two labels and a top level domain made of letters,
+/// optionally followed by a port
+fn is_domain(host: &str) -> bool {
+    let host = match host.rfind(':') {
+        Some(i) if host[i + 1..].parse::<u16>().is_ok() => &host[..i],
+        _ => host,
+    };
+    let labels: Vec<&str> = host.split('.').collect();
+    let tld = labels[labels.len() - 1];
+    labels.len() >= 2
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_alphabetic())
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
+fn is_email(text: &str) -> bool {
+    match text.find('@') {
+        Some(i) => {
+            let (user, domain) = (&text[..i], &text[i + 1..]);
+            !user.is_empty() && !user.contains(&['/', ':'][..]) && is_domain(domain)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, set_permissions, write, Permissions};
+    use std::process;
+
+    #[test]
+    fn paths() {
+        let dir = temp_dir().join(format!("rlaunch-input-{}", process::id()));
+        create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        let script = dir.join("script");
+        write(&file, "").unwrap();
+        write(&script, "#!/bin/sh\n").unwrap();
+        set_permissions(&script, Permissions::from_mode(0o755)).unwrap();
+
+        let classify_path = |path: &std::path::Path| classify(&path.to_string_lossy());
+        assert!(matches!(classify_path(&dir), Some(Input::Path(p)) if p == dir));
+        assert!(matches!(classify_path(&file), Some(Input::Path(p)) if p == file));
+        // executables are run, not opened
+        assert!(matches!(classify_path(&script), Some(Input::Executable(p)) if p == script));
+        assert!(classify_path(&dir.join("missing")).is_none());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn urls_and_mail() {
+        assert!(
+            matches!(classify("example.com/a"), Some(Input::Url(u)) if u == "https://example.com/a")
+        );
+        assert!(matches!(classify("www.example.com"), Some(Input::Url(_))));
+        assert!(classify("example.com").is_none());
+        assert!(
+            matches!(classify("me@example.com"), Some(Input::Mail(u)) if u == "mailto:me@example.com")
+        );
+        assert!(classify("ls -l").is_none());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::mime::glob_match;
+use crate::recent::cache_dir;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{create_dir_all, read, read_dir, rename, write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, UNIX_EPOCH};
+
+/// A directory as it was when it was last read
+#[derive(Debug, Clone)]
+struct Directory {
+    /// The modification time, in nanoseconds since the epoch
+    modified: u128,
+    files: Vec<PathBuf>,
+    subdirectories: Vec<PathBuf>,
+}
+
+/// How many of the best matches are shown, sorting all of them would be slow
+pub const MAX_RESULTS: usize = 500;
+
+type Index = HashMap<PathBuf, Directory>;
+
+/// The files in the roots, read from the index in the cache and updated in the background
+pub struct Locate {
+    paths: Arc<Mutex<Vec<PathBuf>>>,
+    updated: Arc<AtomicBool>,
+    /// Updates and then saves the index
+    thread: JoinHandle<()>,
+}
+
+impl Locate {
+    pub fn new(roots: Vec<PathBuf>, ignore: Vec<String>) -> Self {
+        let index = read_index();
+        let paths = Arc::new(Mutex::new(list_paths(&index, &roots, &ignore)));
+        let updated = Arc::new(AtomicBool::new(false));
+
+        let paths_clone = paths.clone();
+        let updated_clone = updated.clone();
+        let thread = thread::spawn(move || {
+            let now = Instant::now();
+            let index = update_index(&roots, &ignore, &index);
+            write_index(&index);
+            *paths_clone.lock().unwrap() = list_paths(&index, &roots, &ignore);
+            updated_clone.store(true, Ordering::Relaxed);
+            eprintln!(
+                "Finished updating the locate index ({}s)",
+                now.elapsed().as_secs_f64()
+            );
+        });
+        Self {
+            paths,
+            updated,
+            thread,
+        }
+    }
+    /// Every file and directory, directories with a trailing slash
+    pub fn paths(&self) -> MutexGuard<'_, Vec<PathBuf>> {
+        self.paths.lock().unwrap()
+    }
+    /// Whether the index was updated since the last time this was asked
+    pub fn take_updated(&self) -> bool {
+        self.updated.swap(false, Ordering::Relaxed)
+    }
+    /// Waits until the updated index is saved, so that the next run doesn't walk everything again
+    pub fn finish(self) {
+        let _ = self.thread.join();
+    }
+}
+
+fn is_ignored(name: &OsStr, ignore: &[String]) -> bool {
+    ignore
+        .iter()
+        .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+}
+
+/// Lists the paths in the index that are in the roots
+fn list_paths(index: &Index, roots: &[PathBuf], ignore: &[String]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    // only walk from the roots, the index can have directories that were removed since
+    let mut stack: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = stack.pop() {
+        let directory = match index.get(&dir) {
+            Some(directory) => directory,
+            None => continue,
+        };
+        for name in &directory.subdirectories {
+            if !is_ignored(name.as_os_str(), ignore) {
+                let path = dir.join(name);
+                paths.push(path.join(""));
+                stack.push(path);
+            }
+        }
+        for name in &directory.files {
+            if !is_ignored(name.as_os_str(), ignore) {
+                paths.push(dir.join(name));
+            }
+        }
+    }
+    paths.sort();
+    paths
+}
+
+/// Walks the roots, only reading the directories modified since they were last read.
+/// A directory's modification time changes when its entries do, but not when its
+/// subdirectories' entries do, so every directory still has to be checked.
+/// The directories outside the roots are kept for the instances with other roots.
+fn update_index(roots: &[PathBuf], ignore: &[String], old: &Index) -> Index {
+    let mut index = Index::new();
+    let mut stack: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = stack.pop() {
+        if index.contains_key(&dir) {
+            continue;
+        }
+        let modified = match dir.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+            Err(_) => continue,
+        };
+        let directory = match old.get(&dir) {
+            Some(directory) if directory.modified == modified => directory.clone(),
+            _ => match read_directory(&dir, modified) {
+                Some(directory) => directory,
+                None => continue,
+            },
+        };
+        for name in &directory.subdirectories {
+            if !is_ignored(name.as_os_str(), ignore) {
+                stack.push(dir.join(name));
+            }
+        }
+        index.insert(dir, directory);
+    }
+    for (dir, directory) in old {
+        if !roots.iter().any(|root| dir.starts_with(root)) {
+            index.insert(dir.clone(), directory.clone());
+        }
+    }
+    index
+}
+
+/// Reads the entries of the directory, leaving out the hidden subdirectories
+fn read_directory(dir: &Path, modified: u128) -> Option<Directory> {
+    let entries = read_dir(dir).ok()?;
+    let mut directory = Directory {
+        modified,
+        files: Vec::new(),
+        subdirectories: Vec::new(),
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = PathBuf::from(entry.file_name());
+        // names with newlines can't be saved in the index
+        if name.as_os_str().as_bytes().contains(&b'\n') {
+            continue;
+        }
+        // symlinks aren't followed, so that they can't make loops
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => {
+                if !name.as_os_str().as_bytes().starts_with(b".") {
+                    directory.subdirectories.push(name);
+                }
+            }
+            Ok(_) => directory.files.push(name),
+            Err(_) => continue,
+        }
+    }
+    Some(directory)
+}
+
+fn index_path() -> Option<PathBuf> {
+    Some(cache_dir()?.join("locate-index"))
+}
+
+/// The index is saved as a line of `<modified> <path>` for every directory,
+/// followed by a line of `d <name>` for every subdirectory and `f <name>` for every file
+fn read_index() -> Index {
+    let mut index = Index::new();
+    let contents = match index_path().and_then(|path| read(path).ok()) {
+        Some(contents) => contents,
+        None => return index,
+    };
+    let mut current: Option<(PathBuf, Directory)> = None;
+    for line in contents.split(|&c| c == b'\n') {
+        let (kind, rest) = match line.iter().position(|&c| c == b' ') {
+            Some(i) => (&line[..i], PathBuf::from(OsStr::from_bytes(&line[i + 1..]))),
+            None => continue,
+        };
+        match kind {
+            b"d" | b"f" => {
+                if let Some((_, directory)) = &mut current {
+                    if kind == b"d" {
+                        directory.subdirectories.push(rest);
+                    } else {
+                        directory.files.push(rest);
+                    }
+                }
+            }
+            _ => {
+                let modified = match std::str::from_utf8(kind).ok().and_then(|m| m.parse().ok()) {
+                    Some(modified) => modified,
+                    None => continue,
+                };
+                if let Some((path, directory)) = current.take() {
+                    index.insert(path, directory);
+                }
+                current = Some((
+                    rest,
+                    Directory {
+                        modified,
+                        files: Vec::new(),
+                        subdirectories: Vec::new(),
+                    },
+                ));
+            }
+        }
+    }
+    if let Some((path, directory)) = current {
+        index.insert(path, directory);
+    }
+    index
+}
+
+fn write_index(index: &Index) {
+    let path = match index_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let mut contents = Vec::new();
+    for (dir, directory) in index {
+        contents.extend(directory.modified.to_string().as_bytes());
+        contents.push(b' ');
+        contents.extend(dir.as_os_str().as_bytes());
+        contents.push(b'\n');
+        for (kind, names) in &[(b'd', &directory.subdirectories), (b'f', &directory.files)] {
+            for name in names.iter() {
+                contents.push(*kind);
+                contents.push(b' ');
+                contents.extend(name.as_os_str().as_bytes());
+                contents.push(b'\n');
+            }
+        }
+    }
+    // written next to the index and then moved over it, so that it's never read half-written
+    let temporary = path.with_extension(format!("{}.tmp", process::id()));
+    let result = path
+        .parent()
+        .map_or(Ok(()), create_dir_all)
+        .and_then(|_| write(&temporary, contents))
+        .and_then(|_| rename(&temporary, &path));
+    if let Err(e) = result {
+        eprintln!("Couldn't save the locate index: {}", e);
+    }
+}
+
+/// Whether the characters of the needle are all in the haystack in the same order,
+/// ignoring case
+pub fn contains_subsequence(haystack: &str, needle: &str) -> bool {
+    let mut haystack = haystack.chars().flat_map(char::to_lowercase);
+    needle
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|n| haystack.any(|h| h == n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn other_roots_are_kept() {
+        let dir = temp_dir().join(format!("rlaunch-locate-{}", process::id()));
+        // the roots of two instances
+        let (first, second) = ([dir.join("first")], [dir.join("second")]);
+        create_dir_all(first[0].join("sub")).unwrap();
+        create_dir_all(second[0].join(".hidden")).unwrap();
+        create_dir_all(second[0].join("build")).unwrap();
+        write(first[0].join("sub/a.txt"), "").unwrap();
+        write(second[0].join("b.txt"), "").unwrap();
+        let ignore = vec!["build".to_owned()];
+
+        let index = update_index(&first, &ignore, &Index::new());
+        let index = update_index(&second, &ignore, &index);
+        assert_eq!(
+            list_paths(&index, &first, &ignore),
+            vec![first[0].join("sub/"), first[0].join("sub/a.txt")]
+        );
+        // hidden and ignored directories aren't listed
+        assert_eq!(
+            list_paths(&index, &second, &ignore),
+            vec![second[0].join("b.txt")]
+        );
+
+        // the removed directories in the roots are dropped
+        remove_dir_all(first[0].join("sub")).unwrap();
+        let index = update_index(&first, &ignore, &index);
+        assert!(!index.contains_key(&first[0].join("sub")));
+        assert!(index.contains_key(&second[0]));
+        remove_dir_all(&dir).unwrap();
+    }
+}
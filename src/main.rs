@@ -1,18 +1,61 @@
 mod applications;
 mod arguments;
+mod bookmarks;
+mod calculator;
+mod completion;
+mod files;
+mod history;
+mod input;
+mod locate;
+mod mime;
+mod pinentry;
+mod processes;
+mod provider;
+mod recent;
+mod recent_files;
+mod script;
+mod search;
+mod ssh;
+mod unicode;
+mod windows;
+mod workspaces;
 mod x11;
 
-use applications::{read_applications, Apps};
-use arguments::{get_args, Args};
+use applications::{read_applications, read_commands, Apps};
+use arguments::{get_args, Args, Mode};
+use bookmarks::{open_url, read_bookmarks, Bookmark};
+use completion::{split_command, PathCompletion};
+use files::{open_file, FileBrowser};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
-use std::cmp::{max, min};
+use history::{read_history, run_in_shell};
+use input::{classify, Input};
+use locate::{contains_subsequence, Locate, MAX_RESULTS};
+use mime::{applications_for, Associations, MimeDatabase};
+use pinentry::{Dialog, Request};
+use processes::ProcessList;
+use provider::{Provider, ProviderAction, ProviderItem};
+use recent_files::{read_recent_files, RecentFile};
+use script::Script;
+use search::{find_engine, search_engines, SearchEngine};
+use ssh::{read_hosts, record_host, Host};
+use std::cell::OnceCell;
+use std::cmp::{max, min, Reverse};
+use std::env::var;
+use std::io::{self, Write};
+use std::mem::discriminant;
+use std::ops::Range;
+use std::os::raw::c_ulong;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
-use x11::{Action, GraphicsContext, TextRenderingContext, X11Context};
+use std::time::{Duration, Instant};
+use unicode::{read_characters, Character};
+use windows::{find_app_window, read_windows, WindowInfo};
+use workspaces::{read_workspaces, Workspace};
+use x11::{Action, GraphicsContext, TextRenderingContext, Window, X11Context};
 use x11_dl::xlib;
 
 const KEY_ESCAPE: u32 = 9;
@@ -23,38 +66,339 @@ const KEY_DOWN: u32 = 116;
 const KEY_BACKSPACE: u32 = 22;
 const KEY_ENTER: u32 = 36;
 const KEY_TAB: u32 = 23;
+const KEY_HOME: u32 = 110;
+const KEY_END: u32 = 115;
+const KEY_PAGE_UP: u32 = 112;
+const KEY_PAGE_DOWN: u32 = 117;
+const KEY_H: u32 = 43;
 const KEY_K: u32 = 45;
+const KEY_S: u32 = 39;
+const KEY_SPACE: u32 = 65;
 const KEY_U: u32 = 30;
 
+/// Typing one of these at the start of the text switches to the mode until it's removed
+const PREFIXES: &[(char, Mode)] = &[('=', Mode::Calc), ('>', Mode::Run)];
+
+/// What every character of a password is shown as
+const BULLET: char = '•';
+
+/// The longest text that can be typed in the password mode, in bytes. The buffer is allocated
+/// once with this capacity, so that a reallocation never leaves a copy of the password behind.
+const PASSWORD_CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+struct Suggestion {
+    score: i64,
+    name: String,
+    comment: String,
+    target: Target,
+    /// The mode the suggestion came from in the combi mode
+    mode: Option<Mode>,
+}
+
+impl Suggestion {
+    /// The name as shown on the bar, tagged with the mode it came from in the combi mode
+    fn label(&self) -> String {
+        match self.mode {
+            Some(mode) => format!("[{}] {}", mode.name(), self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// What happens when a suggestion is chosen
+#[derive(Clone)]
+enum Target {
+    /// Launch the application with the suggestion's name
+    App,
+    /// Run the suggestion's name as a command, with the arguments typed after it
+    Command,
+    /// Activate the window with the given ID
+    Window(c_ulong),
+    /// Switch to the desktop with the given index
+    Workspace(u32),
+    /// Copy the suggestion's name to the clipboard
+    Copy,
+    /// Open the file or enter the directory at the given path
+    File(PathBuf),
+    /// Connect to the host over SSH in a terminal
+    Ssh(Host),
+    /// Give the script's entry with the given index back to the script
+    Script(usize),
+    /// An item from the provider with the given index
+    Provider(usize, ProviderItem),
+    /// Close the bar, confirming or not
+    Confirm(bool),
+    /// Copy the characters to the clipboard, or type them into the previously focused window
+    Character(&'static str),
+    /// Open the URL in the browser
+    Url(String),
+    /// Compose an email to the mailto: URL with the default mail client
+    Mail(String),
+    /// Open the file with the application with the suggestion's name
+    OpenWith(PathBuf),
+    /// Run the command with the user's shell
+    Shell(String),
+    /// Run the typed command as it is
+    Run(String),
+    /// Open the recently used file with the given index
+    RecentFile(usize),
+    /// Terminate the process with the given PID
+    Process(i32),
+}
+
+/// Everything besides applications that suggestions are made from
+#[derive(Default)]
+struct Sources {
+    windows: Vec<WindowInfo>,
+    workspaces: Vec<Workspace>,
+    /// The window that was focused before rlaunch was started
+    focused_window: Option<c_ulong>,
+    files: Option<FileBrowser>,
+    hosts: Vec<Host>,
+    script: Option<Script>,
+    /// The commands in the PATH, read the first time the run mode is used
+    commands: Option<Vec<String>>,
+    providers: Vec<Provider>,
+    /// The buttons of the confirm mode and whether they confirm
+    buttons: Vec<(String, bool)>,
+    characters: Vec<Character>,
+    bookmarks: Vec<Bookmark>,
+    searches: Vec<SearchEngine>,
+    /// The types of files and the applications for them, read the first time they're needed
+    mime: OnceCell<(MimeDatabase, Associations)>,
+    /// The file given with --open-with and its type
+    open_with: Option<(PathBuf, String)>,
+    /// The commands from the shells' history, the most recent first
+    history: Vec<String>,
+    recent_files: Vec<RecentFile>,
+    /// Refreshed while the bar is open
+    processes: Option<ProcessList>,
+    /// The indexed files, updated in the background
+    locate: Option<Locate>,
+}
+
+impl Sources {
+    fn mime(&self) -> (&MimeDatabase, &Associations) {
+        let (database, associations) = self.mime.get_or_init(|| {
+            let database = MimeDatabase::load();
+            let associations = Associations::load(&database);
+            (database, associations)
+        });
+        (database, associations)
+    }
+}
+
 struct State {
+    /// The modes switched between with Ctrl+Tab and the index of the current one
+    modes: Vec<Mode>,
+    mode_index: usize,
+    prompt: Option<String>,
+    /// Shown instead of the suggestions when there are none
+    message: Option<String>,
     caret_pos: i32,
     text: String,
-    last_text: String,
-    suggestions: Vec<(i64, String)>,
-    selected: u8,
+    // None if the suggestions were never updated yet
+    last_text: Option<String>,
+    // changes whenever the providers' results change
+    providers_generation: u64,
+    suggestions: Vec<Suggestion>,
+    selected: usize,
+    // the index of the first suggestion shown on the bar
+    // and how many suggestions fit starting from it
+    first: usize,
+    visible: usize,
     progress: f32,
     progress_finished: Option<Instant>,
+    /// Text to copy to the clipboard once the bar is closed
+    clipboard: Option<String>,
+    /// Text to type into the previously focused window once the bar is closed
+    typed: Option<String>,
+    /// The path completion being cycled through with Tab
+    completion: Option<PathCompletion>,
+    /// Whether the bar was closed with Escape
+    cancelled: bool,
+    /// The suggestions marked in the multi-select mode, in the order they were marked
+    marked: Vec<Suggestion>,
+}
+
+impl State {
+    fn new(modes: Vec<Mode>, mode_index: usize, prompt: Option<String>) -> Self {
+        Self {
+            modes,
+            mode_index,
+            prompt,
+            message: None,
+            caret_pos: 0,
+            text: String::with_capacity(PASSWORD_CAPACITY),
+            last_text: None,
+            providers_generation: 0,
+            suggestions: Vec::new(),
+            selected: 0,
+            first: 0,
+            visible: 0,
+            progress: 0.0,
+            progress_finished: None,
+            clipboard: None,
+            typed: None,
+            completion: None,
+            cancelled: false,
+            marked: Vec::new(),
+        }
+    }
+    /// The active mode and the text typed for it, without the prefix that switched to it
+    fn active_mode(&self) -> (Mode, &str) {
+        let mode = self.modes[self.mode_index];
+        // a password can start with anything
+        if mode != Mode::Password {
+            for (prefix, mode) in PREFIXES {
+                if let Some(query) = self.text.strip_prefix(*prefix) {
+                    return (*mode, query);
+                }
+            }
+        }
+        (mode, &self.text)
+    }
+    /// The text shown before the input: the prompt if one was given and the name of the active
+    /// mode if it's worth showing, so that switching modes is visible with a prompt too
+    fn prompt(&self) -> Option<String> {
+        let mode = self.active_mode().0;
+        let mode_name = if self.modes.len() > 1 || mode != self.modes[self.mode_index] {
+            Some(mode.name())
+        } else {
+            None
+        };
+        match (&self.prompt, mode_name) {
+            (Some(prompt), Some(mode_name)) => Some(format!("{} [{}]", prompt, mode_name)),
+            (Some(prompt), None) => Some(prompt.clone()),
+            (None, mode_name) => mode_name.map(|m| m.to_owned()),
+        }
+    }
+    fn visible_suggestions(&self) -> impl Iterator<Item = (usize, &Suggestion)> {
+        self.suggestions
+            .iter()
+            .enumerate()
+            .skip(self.first)
+            .take(self.visible)
+    }
+    /// Clears the typed text and makes the suggestions update
+    fn reset_text(&mut self) {
+        self.text.clear();
+        self.caret_pos = 0;
+        self.last_text = None;
+    }
+    fn is_marked(&self, suggestion: &Suggestion) -> bool {
+        self.marked.iter().any(|m| same_suggestion(m, suggestion))
+    }
+    /// Copies the text to the clipboard once the bar is closed, after what was copied before
+    /// when several marked suggestions are chosen
+    fn copy(&mut self, text: &str, separator: &str) {
+        match &mut self.clipboard {
+            Some(clipboard) => {
+                clipboard.push_str(separator);
+                clipboard.push_str(text);
+            }
+            None => self.clipboard = Some(text.to_owned()),
+        }
+    }
+    /// Marks the selected suggestion, or unmarks it if it's already marked
+    fn toggle_mark(&mut self) {
+        let suggestion = match self.suggestions.get(self.selected) {
+            Some(s) => s,
+            None => return,
+        };
+        match self
+            .marked
+            .iter()
+            .position(|m| same_suggestion(m, suggestion))
+        {
+            Some(i) => {
+                self.marked.remove(i);
+            }
+            None => self.marked.push(suggestion.clone()),
+        }
+    }
+    /// Selects the next suggestion, wrapping around to the first one at the end
+    fn select_next(&mut self) {
+        if !self.suggestions.is_empty() {
+            self.selected = (self.selected + 1) % self.suggestions.len();
+        }
+    }
+}
+
+/// The window of the bar and what's needed to draw on it
+struct Bar {
+    window: Window,
+    trc: TextRenderingContext,
+    gc: GraphicsContext,
+    width: u32,
+    font_height: i32,
 }
 
 fn main() {
     let args = get_args();
+
+    if args.pinentry {
+        let stdin = io::stdin();
+        pinentry::serve(
+            stdin.lock(),
+            io::stdout(),
+            &mut PinentryDialog {
+                args: &args,
+                bar: None,
+            },
+        );
+        return;
+    }
+
+    // the modes to switch between, starting with the one given with --mode
+    let mut modes = args.modes.clone();
+    if !modes.contains(&args.mode) {
+        modes.insert(0, args.mode);
+    }
+    let mut mode_index = modes.iter().position(|m| *m == args.mode).unwrap();
+    let mut prompt = args.prompt.clone();
+    if let Some(file) = &args.open_with {
+        if !file.exists() {
+            eprintln!("Error: {:?} doesn't exist", file);
+            exit(1);
+        }
+        modes = vec![Mode::OpenWith];
+        mode_index = 0;
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        prompt = prompt.or_else(|| Some(format!("Open {} with", name)));
+    }
+    // the sources of every mode that can become active have to be read
+    let mut used_modes = modes.clone();
+    if modes.contains(&Mode::Combi) {
+        used_modes.extend(&args.combi_modes);
+    }
+    // prefixes aren't used in passwords
+    if modes.iter().any(|m| *m != Mode::Password) {
+        used_modes.extend(PREFIXES.iter().map(|(_, mode)| *mode));
+    }
+    let uses = |mode| used_modes.contains(&mode);
+    // files are opened with the applications that can open their types
+    let opens_files = uses(Mode::Apps)
+        || uses(Mode::Files)
+        || uses(Mode::OpenWith)
+        || uses(Mode::Recent)
+        || uses(Mode::Locate);
+
     // spawn a thread for reading all applications
     let apps = Arc::new(Mutex::new(Apps::new()));
-    let apps_clone = apps.clone();
-    let path = args.path;
     let progress = Arc::new(Mutex::new((0, 1)));
-    let progress_clone = progress.clone();
-    thread::spawn(move || read_applications(&apps_clone, path, &progress_clone));
-
-    let mut state = State {
-        caret_pos: 0,
-        text: String::new(),
-        last_text: String::new(),
-        suggestions: Vec::new(),
-        selected: 0,
-        progress: 0.0,
-        progress_finished: None,
-    };
+    if opens_files {
+        let apps_clone = apps.clone();
+        let path = args.path;
+        let progress_clone = progress.clone();
+        thread::spawn(move || read_applications(&apps_clone, path, &progress_clone));
+    } else {
+        // nothing to scan
+        *progress.lock().unwrap() = (1, 1);
+    }
+
+    let mut state = State::new(modes, mode_index, prompt);
 
     // initialize xlib context
     let xc = match X11Context::new() {
@@ -65,6 +409,136 @@ fn main() {
         }
     };
 
+    // read the open windows before creating our own
+    let mut sources = Sources {
+        // applications need them too, to switch to the ones already running
+        windows: if uses(Mode::Windows) || uses(Mode::Apps) {
+            read_windows(&xc)
+        } else {
+            Vec::new()
+        },
+        workspaces: if uses(Mode::Workspaces) {
+            read_workspaces(&xc)
+        } else {
+            Vec::new()
+        },
+        focused_window: xc.get_active_window(),
+        files: if uses(Mode::Files) {
+            let directory = args
+                .directory
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(var("HOME").unwrap_or_else(|_| "/".to_owned())));
+            Some(FileBrowser::new(directory, args.hidden))
+        } else {
+            None
+        },
+        hosts: if uses(Mode::Ssh) {
+            read_hosts()
+        } else {
+            Vec::new()
+        },
+        script: if uses(Mode::Script) {
+            match &args.script {
+                Some(command) => Some(Script::new(command)),
+                None => {
+                    eprintln!("Error: the script mode needs a script, given with --script");
+                    exit(1);
+                }
+            }
+        } else {
+            None
+        },
+        commands: None,
+        providers: args
+            .providers
+            .iter()
+            .filter_map(|command| {
+                Provider::spawn(command, Duration::from_millis(args.provider_timeout))
+            })
+            .collect(),
+        buttons: Vec::new(),
+        characters: if uses(Mode::Unicode) {
+            read_characters()
+        } else {
+            Vec::new()
+        },
+        bookmarks: if uses(Mode::Bookmarks) {
+            read_bookmarks()
+        } else {
+            Vec::new()
+        },
+        searches: search_engines(&args.searches),
+        mime: OnceCell::new(),
+        open_with: None,
+        history: if uses(Mode::History) {
+            read_history()
+        } else {
+            Vec::new()
+        },
+        recent_files: if uses(Mode::Recent) {
+            read_recent_files()
+        } else {
+            Vec::new()
+        },
+        processes: if uses(Mode::Processes) {
+            Some(ProcessList::new())
+        } else {
+            None
+        },
+        locate: if uses(Mode::Locate) {
+            let mut roots = args.locate_roots.clone();
+            if roots.is_empty() {
+                roots.push(PathBuf::from(
+                    var("HOME").unwrap_or_else(|_| "/".to_owned()),
+                ));
+            }
+            Some(Locate::new(roots, args.locate_ignore.clone()))
+        } else {
+            None
+        },
+    };
+    if let Some(file) = &args.open_with {
+        sources.open_with = Some((file.clone(), sources.mime().0.mime_type(file)));
+    }
+
+    let bar = create_bar(&xc, &args);
+    run_bar(&xc, &bar, &mut state, &apps, &progress, &mut sources, &args);
+
+    if state.active_mode().0 == Mode::Password {
+        // askpass programs are expected to fail when nothing was entered
+        if state.cancelled {
+            exit(1);
+        }
+        print_password(&mut state.text);
+    }
+
+    if let Some(text) = &state.typed {
+        // the keyboard has to be released for the focused window to get the keys
+        xc.hide_window(&bar.window);
+        xc.type_text(text);
+    }
+
+    if let Some(text) = state.clipboard {
+        xc.hide_window(&bar.window);
+        // keep serving the clipboard in the background, while this process saves the index
+        if unsafe { libc::fork() } <= 0 {
+            xc.serve_clipboard(&bar.window, &text);
+            return;
+        }
+    }
+
+    // the first update of a large index can take longer than the bar was open
+    if let Some(locate) = sources.locate.take() {
+        xc.hide_window(&bar.window);
+        locate.finish();
+    }
+}
+
+/// Creates the window of the bar on the screen with the mouse, without showing it yet
+fn create_bar(xc: &X11Context, args: &Args) -> Bar {
+    // in the vertical layout the window grows downward, one line per suggestion
+    let window_height = args.height * (args.lines + 1);
+
     // get screen width and the position where to map window
     let mut screen_width = 0;
     let mut window_pos = (0, 0);
@@ -80,7 +554,7 @@ fn main() {
             screen_width = screen.width as u32;
             window_pos.0 = screen.x_org as i32;
             window_pos.1 = if args.bottom {
-                screen.y_org as i32 + screen.height as i32 - args.height as i32
+                screen.y_org as i32 + screen.height as i32 - window_height as i32
             } else {
                 screen.y_org as i32
             };
@@ -89,15 +563,13 @@ fn main() {
     }
 
     // create the window
-    let window = xc.create_window(window_pos, screen_width, args.height);
-
-    xc.grab_keyboard();
+    let window = xc.create_window(window_pos, screen_width, window_height);
 
     let font_height = {
         let mut h = 12;
         for x in args.font.split(':') {
-            if x.starts_with("size=") {
-                h = (&x[5..]).parse().expect("couldn't parse font size");
+            if let Some(size) = x.strip_prefix("size=") {
+                h = size.parse().expect("couldn't parse font size");
                 break;
             }
         }
@@ -106,30 +578,155 @@ fn main() {
     let mut trc = xc.init_trc(&window, &format!("{}:size=12:antialias=true", args.font));
     xc.add_color_to_trc(&mut trc, args.color2);
     xc.add_color_to_trc(&mut trc, args.color3);
+    xc.add_color_to_trc(&mut trc, args.color5);
+    xc.add_color_to_trc(&mut trc, args.color6);
+    xc.add_color_to_trc(&mut trc, args.color8);
 
     let gc = xc.init_gc(&window);
 
+    Bar {
+        window,
+        trc,
+        gc,
+        width: screen_width,
+        font_height,
+    }
+}
+
+/// Shows the bar and handles the input until it's closed
+fn run_bar(
+    xc: &X11Context,
+    bar: &Bar,
+    state: &mut State,
+    apps: &Mutex<Apps>,
+    progress: &Mutex<(u32, u32)>,
+    sources: &mut Sources,
+    args: &Args,
+) {
+    xc.grab_keyboard();
+
     // show window
-    xc.map_window(&window);
+    xc.map_window(&bar.window);
 
     xc.run(|xc, event| {
-        update_suggestions(&xc, &trc, &mut state, screen_width, &apps);
-        if state.progress_finished == None {
+        update_suggestions(state, apps, sources, &args.combi_modes);
+        update_scroll(xc, &bar.trc, state, bar.width, args.lines);
+        if state.progress_finished.is_none() {
             let progress_lock = progress.lock().unwrap();
             state.progress = progress_lock.0 as f32 / progress_lock.1 as f32;
             drop(progress_lock);
             if 1.0 - state.progress < 0.000_001 {
                 state.progress_finished = Some(Instant::now());
+                // show the applications that weren't read yet when the text was typed
+                state.last_text = None;
             }
         }
-        render_bar(&xc, &trc, &gc, screen_width, &state, &args, font_height);
+        render_bar(
+            xc,
+            &bar.trc,
+            &bar.gc,
+            bar.width,
+            state,
+            args,
+            bar.font_height,
+        );
         match event {
             None => Action::Run,
-            Some(e) => handle_event(&xc, e, &mut state, &apps, &args.terminal),
+            Some(e) => handle_event(xc, e, state, apps, sources, args),
         }
     });
 }
 
+/// Shows pinentry's requests on the bar
+struct PinentryDialog<'a> {
+    args: &'a Args,
+    /// Opened when it's needed for the first time
+    bar: Option<(X11Context, Bar)>,
+}
+
+impl PinentryDialog<'_> {
+    /// Shows the bar in the given mode, returns the state it was closed with
+    fn show(
+        &mut self,
+        mode: Mode,
+        prompt: Option<String>,
+        message: Option<String>,
+        sources: &mut Sources,
+    ) -> Option<State> {
+        if self.bar.is_none() {
+            let xc = match X11Context::new() {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("Error: {:?}", e);
+                    return None;
+                }
+            };
+            let bar = create_bar(&xc, self.args);
+            self.bar = Some((xc, bar));
+        }
+        let (xc, bar) = self.bar.as_ref().unwrap();
+
+        let mut state = State::new(vec![mode], 0, prompt);
+        state.message = message;
+        // nothing to scan
+        let progress = Mutex::new((1, 1));
+        run_bar(
+            xc,
+            bar,
+            &mut state,
+            &Mutex::new(Apps::new()),
+            &progress,
+            sources,
+            self.args,
+        );
+        xc.hide_window(&bar.window);
+        Some(state)
+    }
+}
+
+impl Dialog for PinentryDialog<'_> {
+    fn get_pin(&mut self, request: &Request) -> Option<String> {
+        let prompt = request.prompt.clone().or_else(|| Some("PIN:".to_owned()));
+        // the error of the previous attempt goes first, the bar has only one line
+        let message: Vec<&str> = request
+            .error
+            .iter()
+            .chain(&request.description)
+            .map(|s| s.as_str())
+            .collect();
+        let message = Some(message.join(" ").replace('\n', " ")).filter(|m| !m.is_empty());
+        let mut state = self.show(Mode::Password, prompt, message, &mut Sources::default())?;
+        if state.cancelled {
+            return None;
+        }
+        Some(std::mem::take(&mut state.text))
+    }
+    fn confirm(&mut self, request: &Request, one_button: bool) -> bool {
+        let ok = request.ok.clone().unwrap_or_else(|| "OK".to_owned());
+        let cancel = request
+            .cancel
+            .clone()
+            .unwrap_or_else(|| "Cancel".to_owned());
+        let mut buttons = vec![(ok, true)];
+        if !one_button {
+            buttons.push((cancel, false));
+        }
+        let mut sources = Sources {
+            buttons,
+            ..Default::default()
+        };
+        let prompt = request
+            .description
+            .as_ref()
+            .or(request.prompt.as_ref())
+            .map(|p| p.replace('\n', " "));
+        match self.show(Mode::Confirm, prompt, None, &mut sources) {
+            Some(state) => !state.cancelled,
+            None => false,
+        }
+    }
+}
+
 fn render_bar(
     xc: &X11Context,
     trc: &TextRenderingContext,
@@ -141,7 +738,7 @@ fn render_bar(
 ) {
     let text_y = args.height as i32 / 2 + font_height / 2;
     // clear
-    xc.draw_rect(&gc, args.color0, 0, 0, width, args.height);
+    xc.draw_rect(gc, args.color0, 0, 0, width, args.height * (args.lines + 1));
 
     // render the scanning progress bar
     if match state.progress_finished {
@@ -166,7 +763,7 @@ fn render_bar(
             progress_bar_color = (r << 16) + (g << 8) + b;
         }
         xc.draw_rect(
-            &gc,
+            gc,
             progress_bar_color,
             0,
             0,
@@ -175,72 +772,613 @@ fn render_bar(
         );
     }
 
+    // render the prompt before the typed text
+    let prompt_width = prompt_width(xc, trc, state);
+    if let Some(prompt) = state.prompt() {
+        xc.draw_rect(gc, args.color7, 0, 0, prompt_width as u32, args.height);
+        xc.render_text(trc, 3, 8, text_y, &prompt);
+    }
+    let input_x = if prompt_width > 0 {
+        prompt_width + 8
+    } else {
+        0
+    };
+    // passwords are shown as bullets
+    let bullets;
+    let (text, before_caret) = if state.active_mode().0 == Mode::Password {
+        bullets = BULLET.to_string().repeat(state.text.chars().count());
+        let caret_bullets = state.text[0..state.caret_pos as usize].chars().count();
+        (&bullets[..], &bullets[..caret_bullets * BULLET.len_utf8()])
+    } else {
+        (&state.text[..], &state.text[0..state.caret_pos as usize])
+    };
     // render the typed text
-    xc.render_text(&trc, 0, 0, text_y, &state.text);
+    xc.render_text(trc, 0, input_x, text_y, text);
     // and the caret
     xc.draw_rect(
-        &gc,
+        gc,
         0xFFFFFF,
-        xc.get_text_dimensions(&trc, &state.text[0..state.caret_pos as usize])
-            .0 as i32,
+        input_x + xc.get_text_dimensions(trc, before_caret).0 as i32,
         2,
         2,
         args.height - 4,
     );
 
     // render suggestions
-    let mut x = (width as f32 * 0.3).floor() as i32;
-    for (i, suggestion) in state.suggestions.iter().enumerate() {
-        let name_width = xc.get_text_dimensions(&trc, &suggestion.1).0 as i32;
+    if args.lines > 0 {
+        render_suggestions_vertical(xc, trc, gc, width, state, args, text_y);
+        return;
+    }
+    let (start, max_width) = suggestions_area(xc, trc, width, state);
+    if state.suggestions.is_empty() {
+        if let Some(message) = &state.message {
+            xc.render_text(trc, 2, start, text_y, message);
+        }
+    }
+    // show indicators if there are more suggestions to the left or right
+    if state.first > 0 {
+        xc.render_text(trc, 1, start - indicator_width(xc, trc) + 4, text_y, "<");
+    }
+    if state.first + state.visible < state.suggestions.len() {
+        xc.render_text(trc, 1, start + max_width + 4, text_y, ">");
+    }
+    let mut x = start;
+    for (i, suggestion) in state.visible_suggestions() {
+        let label = suggestion.label();
+        let name_width = xc.get_text_dimensions(trc, &label).0 as i32;
         // if selected, render rectangle below
-        if state.selected as usize == i {
-            xc.draw_rect(&gc, args.color1, x, 0, name_width as u32 + 16, args.height);
+        if state.selected == i {
+            xc.draw_rect(gc, args.color1, x, 0, name_width as u32 + 16, args.height);
         }
 
-        xc.render_text(&trc, 1, x + 8, text_y, &suggestion.1);
+        let color = if state.is_marked(suggestion) { 4 } else { 1 };
+        xc.render_text(trc, color, x + 8, text_y, &label);
 
         x += name_width + 16;
     }
 }
 
+fn render_suggestions_vertical(
+    xc: &X11Context,
+    trc: &TextRenderingContext,
+    gc: &GraphicsContext,
+    width: u32,
+    state: &State,
+    args: &Args,
+    text_y: i32,
+) {
+    // comments are aligned in a column at the same place
+    // where the suggestions start in the horizontal layout
+    let comment_column = (width as f32 * 0.3).floor() as i32 + prompt_width(xc, trc, state);
+    // show the position in the list if not all suggestions fit
+    if state.visible < state.suggestions.len() {
+        let position = format!("{}/{}", state.selected + 1, state.suggestions.len());
+        let position_width = xc.get_text_dimensions(trc, &position).0 as i32;
+        xc.render_text(trc, 2, width as i32 - position_width - 8, text_y, &position);
+    }
+    // the message takes the rows of the suggestions, a line on each
+    if state.suggestions.is_empty() {
+        if let Some(message) = &state.message {
+            for (row, line) in message.lines().take(args.lines as usize).enumerate() {
+                let y = args.height as i32 * (row as i32 + 1);
+                xc.render_text(trc, 2, 8, y + text_y, line);
+            }
+        }
+    }
+    for (i, suggestion) in state.visible_suggestions() {
+        let y = args.height as i32 * ((i - state.first) as i32 + 1);
+        // if selected, highlight the whole line
+        if state.selected == i {
+            xc.draw_rect(gc, args.color1, 0, y, width, args.height);
+        }
+
+        let label = suggestion.label();
+        let color = if state.is_marked(suggestion) { 4 } else { 1 };
+        xc.render_text(trc, color, 8, y + text_y, &label);
+
+        let name_width = xc.get_text_dimensions(trc, &label).0 as i32;
+        let comment_x = max(comment_column, name_width + 24);
+        xc.render_text(trc, 2, comment_x, y + text_y, &suggestion.comment);
+    }
+}
+
 fn update_suggestions(
+    state: &mut State,
+    apps: &Mutex<applications::Apps>,
+    sources: &mut Sources,
+    combi_modes: &[Mode],
+) {
+    // there is nothing to suggest for a password, and it mustn't be given to the providers
+    if state.active_mode().0 == Mode::Password {
+        state.suggestions.clear();
+        return;
+    }
+    let text_changed = state.last_text.as_ref() != Some(&state.text);
+    if text_changed {
+        for provider in &mut sources.providers {
+            provider.query(&state.text);
+        }
+    }
+    let mut providers_generation = 0;
+    let mut provider_items = Vec::new();
+    for provider in &mut sources.providers {
+        provider.check_timeout();
+        let (generation, items) = provider.items();
+        providers_generation += generation;
+        provider_items.push(items);
+    }
+    let processes_refreshed = match &mut sources.processes {
+        Some(processes) => processes.refresh(),
+        None => false,
+    };
+    let locate_updated = sources.locate.as_ref().is_some_and(|l| l.take_updated());
+    if !text_changed
+        && providers_generation == state.providers_generation
+        && !processes_refreshed
+        && !locate_updated
+    {
+        return;
+    }
+    state.providers_generation = providers_generation;
+
+    if text_changed {
+        state.selected = 0;
+        state.first = 0;
+    }
+    let (mode, query) = state.active_mode();
+    // scanning the PATH takes a while, so it's only done if it's needed
+    let runs = mode == Mode::Run || (mode == Mode::Combi && combi_modes.contains(&Mode::Run));
+    if runs && sources.commands.is_none() {
+        sources.commands = Some(read_commands());
+    }
+    let mut suggestions = if mode == Mode::Combi {
+        let mut suggestions = Vec::new();
+        for &combi_mode in combi_modes.iter().filter(|m| **m != Mode::Combi) {
+            // tag the suggestions with the mode they came from
+            for mut suggestion in mode_suggestions(combi_mode, query, apps, sources) {
+                suggestion.mode = Some(combi_mode);
+                suggestions.push(suggestion);
+            }
+        }
+        suggestions
+    } else {
+        mode_suggestions(mode, query, apps, sources)
+    };
+    // the providers do their own matching
+    for (i, items) in provider_items.into_iter().enumerate() {
+        for item in items {
+            suggestions.push(Suggestion {
+                score: item.score,
+                name: item.label.clone(),
+                comment: item.comment.clone(),
+                target: Target::Provider(i, item),
+                mode: None,
+            });
+        }
+    }
+    // sort the suggestions by match scores (descending),
+    // keeping the order they came in for equal scores
+    suggestions.sort_by_key(|s| Reverse(s.score));
+    // keep the same process selected when the list is refreshed
+    if let Some(Target::Process(pid)) = state.suggestions.get(state.selected).map(|s| &s.target) {
+        if !text_changed {
+            let pid = *pid;
+            let position = suggestions
+                .iter()
+                .position(|s| matches!(s.target, Target::Process(p) if p == pid));
+            state.selected = position.unwrap_or(state.selected);
+        }
+    }
+    state.suggestions = suggestions;
+
+    // results streaming in shouldn't move the selection out of the suggestions
+    state.selected = min(state.selected, state.suggestions.len().saturating_sub(1));
+    state.last_text = Some(state.text.clone());
+}
+
+/// Finds the suggestions of the mode that match the typed text
+fn mode_suggestions(
+    mode: Mode,
+    query: &str,
+    apps: &Mutex<applications::Apps>,
+    sources: &Sources,
+) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    let matcher = SkimMatcherV2::default();
+    let pattern = query.split_whitespace().collect::<String>();
+    match mode {
+        Mode::Apps => {
+            // iterate over application names
+            // and find those that match the typed text
+            let apps_lock = apps.lock().unwrap();
+            for (name, app) in apps_lock.iter() {
+                if let Some(mtch) = matcher.fuzzy_match(name, &pattern) {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: name.to_string(),
+                        // show the command itself if the application has no comment
+                        comment: if app.comment.is_empty() {
+                            app.exec.clone()
+                        } else {
+                            app.comment.clone()
+                        },
+                        target: Target::App,
+                        mode: None,
+                    });
+                }
+            }
+            // if the text is a math expression, its result goes first
+            suggestions.extend(mode_suggestions(Mode::Calc, query, apps, sources));
+            // and so does opening the text, if it's a URL, a file or an email address
+            suggestions.extend(input_suggestions(query));
+            // or a search, if it starts with a keyword
+            if let Some((engine, search)) = find_engine(&sources.searches, query) {
+                let url = engine.url(search);
+                suggestions.push(Suggestion {
+                    score: i64::MAX,
+                    name: format!("Search {} for {}", engine.name(), search),
+                    comment: url.clone(),
+                    target: Target::Url(url),
+                    mode: None,
+                });
+            }
+        }
+        Mode::Windows => {
+            for window in &sources.windows {
+                // match the window class too, since titles often don't mention the application
+                if let Some(mtch) = matcher
+                    .fuzzy_match(&window.title, &pattern)
+                    .or_else(|| matcher.fuzzy_match(&window.class, &pattern))
+                {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: window.title.clone(),
+                        comment: window.description(),
+                        target: Target::Window(window.id),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Workspaces => {
+            for workspace in &sources.workspaces {
+                if let Some(mtch) = matcher.fuzzy_match(&workspace.name, &pattern) {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: workspace.name.clone(),
+                        comment: workspace.description(),
+                        target: Target::Workspace(workspace.index),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Files => {
+            if let Some(browser) = &sources.files {
+                for entry in &browser.entries {
+                    if let Some(mtch) = matcher.fuzzy_match(&entry.name, &pattern) {
+                        let path = browser.directory.join(&entry.name);
+                        suggestions.push(Suggestion {
+                            score: mtch,
+                            name: if entry.is_dir {
+                                format!("{}/", entry.name)
+                            } else {
+                                entry.name.clone()
+                            },
+                            comment: path.to_string_lossy().into_owned(),
+                            target: Target::File(path),
+                            mode: None,
+                        });
+                    }
+                }
+            }
+        }
+        Mode::Ssh => {
+            for host in &sources.hosts {
+                let name = host.display_name();
+                if let Some(mtch) = matcher.fuzzy_match(&name, &pattern) {
+                    suggestions.push(Suggestion {
+                        score: mtch + host.recency_bonus(),
+                        name,
+                        comment: host.source.to_owned(),
+                        target: Target::Ssh(host.clone()),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Script => {
+            if let Some(script) = &sources.script {
+                for (i, entry) in script.entries.iter().enumerate() {
+                    let meta = entry.meta.as_deref().unwrap_or("");
+                    if let Some(mtch) = matcher
+                        .fuzzy_match(&entry.text, &pattern)
+                        .or_else(|| matcher.fuzzy_match(meta, &pattern))
+                    {
+                        suggestions.push(Suggestion {
+                            score: mtch,
+                            name: entry.text.clone(),
+                            comment: String::new(),
+                            target: Target::Script(i),
+                            mode: None,
+                        });
+                    }
+                }
+            }
+        }
+        Mode::Calc => {
+            if let Some(result) = calculator::evaluate(query) {
+                suggestions.push(Suggestion {
+                    score: i64::MAX,
+                    name: result,
+                    comment: "Copy to the clipboard".to_owned(),
+                    target: Target::Copy,
+                    mode: None,
+                });
+            }
+        }
+        Mode::Run => {
+            // only the command is matched, the rest are its arguments
+            let command = query.split_whitespace().next().unwrap_or("");
+            for name in sources.commands.iter().flatten() {
+                if let Some(mtch) = matcher.fuzzy_match(name, command) {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: name.clone(),
+                        comment: String::new(),
+                        target: Target::Command,
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Unicode => {
+            for character in &sources.characters {
+                // the keywords find characters whose names don't describe what they're used for
+                if let Some(mtch) = matcher.fuzzy_match(character.name, &pattern).or_else(|| {
+                    character
+                        .keywords
+                        .iter()
+                        .filter_map(|keyword| matcher.fuzzy_match(keyword, &pattern))
+                        .max()
+                }) {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: format!("{}  {}", character.characters, character.name),
+                        comment: character.keywords.join(", "),
+                        target: Target::Character(character.characters),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Bookmarks => {
+            for bookmark in &sources.bookmarks {
+                if let Some(mtch) = matcher
+                    .fuzzy_match(&bookmark.title, &pattern)
+                    .or_else(|| matcher.fuzzy_match(&bookmark.url, &pattern))
+                {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        // some pages have no title
+                        name: if bookmark.title.is_empty() {
+                            bookmark.url.clone()
+                        } else {
+                            bookmark.title.clone()
+                        },
+                        comment: format!("{} ({})", bookmark.url, bookmark.source),
+                        target: Target::Url(bookmark.url.clone()),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::History => {
+            for command in &sources.history {
+                if let Some(mtch) = matcher.fuzzy_match(command, &pattern) {
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        // multi-line commands are shown on one line
+                        name: command.replace('\n', " ↵ "),
+                        comment: String::new(),
+                        target: Target::Shell(command.clone()),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Recent => {
+            for (i, file) in sources.recent_files.iter().enumerate() {
+                let path = file.path.to_string_lossy();
+                if let Some(mtch) = matcher.fuzzy_match(&path, &pattern) {
+                    let mut comment = file.mime_type.clone();
+                    if let Some((application, _)) = &file.application {
+                        comment = format!("{}, opened with {}", comment, application);
+                    }
+                    suggestions.push(Suggestion {
+                        score: mtch,
+                        name: path.into_owned(),
+                        comment,
+                        target: Target::RecentFile(i),
+                        mode: None,
+                    });
+                }
+            }
+        }
+        Mode::Processes => {
+            if let Some(list) = &sources.processes {
+                for process in &list.processes {
+                    if let Some(mtch) = matcher
+                        .fuzzy_match(&process.name, &pattern)
+                        .or_else(|| matcher.fuzzy_match(&process.command, &pattern))
+                    {
+                        suggestions.push(Suggestion {
+                            score: mtch,
+                            name: process.description(),
+                            comment: process.command.clone(),
+                            target: Target::Process(process.pid),
+                            mode: None,
+                        });
+                    }
+                }
+            }
+        }
+        Mode::Locate => {
+            // every indexed file would match
+            if let (Some(locate), false) = (&sources.locate, pattern.is_empty()) {
+                let home = var("HOME").ok().map(PathBuf::from);
+                let mut found = Vec::new();
+                for path in locate.paths().iter() {
+                    let full = path.to_string_lossy();
+                    // much faster than the fuzzy matching, which most paths wouldn't pass
+                    if !contains_subsequence(&full, &pattern) {
+                        continue;
+                    }
+                    let name = match home.as_ref().and_then(|h| path.strip_prefix(h).ok()) {
+                        Some(relative) => format!("~/{}", relative.to_string_lossy()),
+                        None => full.into_owned(),
+                    };
+                    let basename = path.file_name().unwrap_or_default().to_string_lossy();
+                    // matches in the file's own name count more than in its directories
+                    if let Some(mtch) = matcher.fuzzy_match(&name, &pattern) {
+                        let score =
+                            mtch + 2 * matcher.fuzzy_match(&basename, &pattern).unwrap_or(0);
+                        found.push(Suggestion {
+                            score,
+                            name,
+                            comment: String::new(),
+                            target: Target::File(path.clone()),
+                            mode: None,
+                        });
+                    }
+                }
+                found.sort_by_key(|s| Reverse(s.score));
+                found.truncate(MAX_RESULTS);
+                suggestions.extend(found);
+            }
+        }
+        Mode::OpenWith => {
+            if let Some((path, mime)) = &sources.open_with {
+                let (database, associations) = sources.mime();
+                let apps_lock = apps.lock().unwrap();
+                let names = applications_for(database, associations, &apps_lock, mime);
+                for (i, name) in names.into_iter().enumerate() {
+                    if let Some(mtch) = matcher.fuzzy_match(&name, &pattern) {
+                        suggestions.push(Suggestion {
+                            score: mtch,
+                            comment: if i == 0 {
+                                format!("Default for {}", mime)
+                            } else {
+                                apps_lock[&name].comment.clone()
+                            },
+                            name,
+                            target: Target::OpenWith(path.clone()),
+                            mode: None,
+                        });
+                    }
+                }
+            }
+        }
+        Mode::Confirm => {
+            // the buttons are always shown, whatever is typed
+            for (name, confirms) in &sources.buttons {
+                suggestions.push(Suggestion {
+                    score: 0,
+                    name: name.clone(),
+                    comment: String::new(),
+                    target: Target::Confirm(*confirms),
+                    mode: None,
+                });
+            }
+        }
+        // combi mode is made of the other modes
+        Mode::Combi | Mode::Password => {}
+    }
+    suggestions
+}
+
+/// Scrolls the shown part of the suggestions so that the selected one is always visible
+fn update_scroll(
     xc: &X11Context,
     trc: &TextRenderingContext,
     state: &mut State,
     width: u32,
-    apps: &Mutex<applications::Apps>,
+    lines: u32,
 ) {
-    if state.text == state.last_text {
+    if state.suggestions.is_empty() {
+        state.visible = 0;
         return;
     }
-    state.suggestions.clear();
-    // iterate over application names
-    // and find those that match the typed text
-    let mut x = 0;
-    let max_width = (width as f32 * 0.7).floor() as i32;
-    let apps_lock = apps.lock().unwrap();
-    for app in apps_lock.iter() {
-        let name = &app.0;
-        if let Some(mtch) = SkimMatcherV2::default()
-            .fuzzy_match(name, &state.text.split_whitespace().collect::<String>())
-        {
-            state.suggestions.push((mtch, name.to_string()));
+    if state.selected < state.first {
+        state.first = state.selected;
+    }
+
+    // in the vertical layout just show as many suggestions as there are lines
+    if lines > 0 {
+        let lines = lines as usize;
+        if state.selected >= state.first + lines {
+            state.first = state.selected + 1 - lines;
         }
+        state.visible = min(lines, state.suggestions.len() - state.first);
+        return;
     }
-    // sort the suggestion by match scores (descending) and name (ascending)
-    state.suggestions.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
 
-    for (i, suggestion) in state.suggestions.iter().enumerate() {
-        let name = &suggestion.1;
-        let width = xc.get_text_dimensions(&trc, &name).0 as i32;
-        if x + width <= max_width {
-            x += width + 16;
-        } else {
-            state.suggestions.truncate(i + 1);
-            break;
+    let max_width = suggestions_area(xc, trc, width, state).1;
+    let suggestion_width = |s: &Suggestion| xc.get_text_dimensions(trc, &s.label()).0 as i32 + 16;
+    let count_fitting = |suggestions: &[Suggestion]| {
+        let mut x = 0;
+        let mut count = 0;
+        for suggestion in suggestions {
+            x += suggestion_width(suggestion);
+            // always show at least one suggestion, even if it doesn't fit
+            if x > max_width && count > 0 {
+                break;
+            }
+            count += 1;
         }
+        count
+    };
+
+    state.visible = count_fitting(&state.suggestions[state.first..]);
+    if state.selected >= state.first + state.visible {
+        // scroll right until the selected suggestion is the last one that fits
+        let mut x = suggestion_width(&state.suggestions[state.selected]);
+        state.first = state.selected;
+        while state.first > 0 {
+            x += suggestion_width(&state.suggestions[state.first - 1]);
+            if x > max_width {
+                break;
+            }
+            state.first -= 1;
+        }
+        state.visible = count_fitting(&state.suggestions[state.first..]);
     }
-    state.last_text = state.text.clone();
+}
+
+/// Returns the x coordinate where the horizontal suggestions start and their maximum total width,
+/// leaving space on both sides for the scrolling indicators
+fn suggestions_area(
+    xc: &X11Context,
+    trc: &TextRenderingContext,
+    width: u32,
+    state: &State,
+) -> (i32, i32) {
+    let indicator_width = indicator_width(xc, trc);
+    let start =
+        (width as f32 * 0.3).floor() as i32 + prompt_width(xc, trc, state) + indicator_width;
+    (start, width as i32 - start - indicator_width)
+}
+
+/// The width of the prompt label, 0 if there is none
+fn prompt_width(xc: &X11Context, trc: &TextRenderingContext, state: &State) -> i32 {
+    match state.prompt() {
+        Some(prompt) => xc.get_text_dimensions(trc, &prompt).0 as i32 + 16,
+        None => 0,
+    }
+}
+
+fn indicator_width(xc: &X11Context, trc: &TextRenderingContext) -> i32 {
+    xc.get_text_dimensions(trc, ">").0 as i32 + 8
 }
 
 fn handle_event(
@@ -248,69 +1386,170 @@ fn handle_event(
     event: &xlib::XEvent,
     state: &mut State,
     apps: &Mutex<applications::Apps>,
-    terminal: &str,
+    sources: &mut Sources,
+    args: &Args,
 ) -> Action {
     if let Some(e) = xc.xevent_to_xkeyevent(*event) {
         let ctrl = (e.state & xlib::ControlMask) != 0;
+        let shift = (e.state & xlib::ShiftMask) != 0;
+        // any other key than Tab finishes the path completion
+        if e.keycode != KEY_TAB {
+            state.completion = None;
+        }
         match e.keycode {
             KEY_ESCAPE => {
+                if state.active_mode().0 == Mode::Password {
+                    zero_text(&mut state.text);
+                }
+                state.cancelled = true;
                 return Action::Stop;
             }
             KEY_LEFT => {
                 if state.selected == 0 {
-                    state.caret_pos = max(0, state.caret_pos - 1);
+                    state.caret_pos =
+                        prev_char_boundary(&state.text, state.caret_pos as usize) as i32;
                 } else {
                     state.selected -= 1;
                 }
             }
             KEY_UP => {
-                state.selected = max(0, state.selected as i16 - 1) as u8;
+                // wrap around to the last suggestion
+                if state.selected == 0 {
+                    state.selected = state.suggestions.len().saturating_sub(1);
+                } else {
+                    state.selected -= 1;
+                }
             }
             KEY_DOWN => {
-                state.selected = min(state.selected + 1, state.suggestions.len() as u8 - 1);
+                state.select_next();
             }
             KEY_RIGHT => {
                 if state.caret_pos == state.text.len() as i32 {
-                    state.selected = min(state.selected + 1, state.suggestions.len() as u8 - 1);
+                    state.select_next();
                 } else {
-                    state.caret_pos += 1;
+                    state.caret_pos =
+                        next_char_boundary(&state.text, state.caret_pos as usize) as i32;
                 }
             }
+            KEY_PAGE_UP => {
+                state.selected = state.selected.saturating_sub(max(state.visible, 1));
+            }
+            KEY_PAGE_DOWN => {
+                state.selected = min(
+                    state.selected + max(state.visible, 1),
+                    state.suggestions.len().saturating_sub(1),
+                );
+            }
+            KEY_HOME => {
+                state.selected = 0;
+            }
+            KEY_END => {
+                state.selected = state.suggestions.len().saturating_sub(1);
+            }
             KEY_BACKSPACE => {
                 if state.caret_pos != 0 {
-                    state.text.remove(state.caret_pos as usize - 1);
-                    state.caret_pos -= 1;
+                    let prev = prev_char_boundary(&state.text, state.caret_pos as usize);
+                    remove_text(&mut state.text, prev..state.caret_pos as usize);
+                    state.caret_pos = prev as i32;
                     state.selected = 0;
+                } else if let Some(browser) = &mut sources.files {
+                    // go up one level in the file browser
+                    if state.text.is_empty() && state.active_mode().0 == Mode::Files {
+                        browser.up();
+                        state.reset_text();
+                    }
+                }
+            }
+            KEY_H if ctrl => {
+                if let Some(browser) = &mut sources.files {
+                    browser.toggle_hidden();
+                    state.last_text = None;
+                }
+            }
+            KEY_S if ctrl => {
+                if let Some(browser) = &mut sources.files {
+                    browser.toggle_sort();
+                    state.last_text = None;
                 }
             }
             KEY_U if ctrl => {
-                state.text = state.text.split_off(state.caret_pos as usize);
+                remove_text(&mut state.text, 0..state.caret_pos as usize);
                 state.caret_pos = 0;
             }
             KEY_K if ctrl => {
-                state.text.truncate(state.caret_pos as usize);
+                let end = state.text.len();
+                remove_text(&mut state.text, state.caret_pos as usize..end);
                 state.selected = 0;
             }
+            KEY_SPACE if ctrl && args.multi_select => {
+                state.toggle_mark();
+                state.select_next();
+            }
             KEY_ENTER => {
+                let (mode, query) = state.active_mode();
+                // applications are launched, everything else is printed for scripts to read
+                if !state.marked.is_empty() {
+                    for suggestion in &state.marked {
+                        if let Target::App = suggestion.target {
+                            launch_app(xc, apps, &suggestion.name, sources, args, false);
+                        } else {
+                            println!("{}", suggestion.name);
+                        }
+                    }
+                    return Action::Stop;
+                }
+                // the password is taken once the bar is closed
+                if mode == Mode::Password {
+                    return Action::Stop;
+                }
                 // if no suggestions available, just run the text, otherwise launch selected application
                 if state.suggestions.is_empty() {
-                    run_command(&state.text);
-                } else {
-                    let apps_lock = apps.lock().unwrap();
-                    let app = &apps_lock
-                        .get(&state.suggestions[state.selected as usize].1)
-                        .unwrap();
-                    if app.show_terminal {
-                        run_command(&format!("{} -e \"{}\"", terminal, app.exec));
-                    } else {
-                        run_command(&app.exec);
+                    // scripts handle custom text themselves
+                    if mode == Mode::Script {
+                        let script = sources.script.as_mut().unwrap();
+                        if script.select(None, &state.text) {
+                            state.reset_text();
+                            return Action::Run;
+                        }
+                        return Action::Stop;
                     }
+                    if mode == Mode::Run {
+                        run_command(query);
+                    } else if mode != Mode::Calc {
+                        // URLs, files and email addresses are opened instead of run
+                        match classify(query) {
+                            Some(Input::Url(url)) => open_url(&url, args.browser.as_deref()),
+                            Some(Input::Path(path)) => open_path(&path, apps, sources, state, args),
+                            Some(Input::Mail(url)) => open_file(Path::new(&url)),
+                            Some(Input::Executable(_)) | None => run_command(query),
+                        }
+                    }
+                    return Action::Stop;
                 }
-                return Action::Stop;
+                let suggestion = state.suggestions[state.selected].clone();
+                return choose(xc, &suggestion, state, apps, sources, args, shift);
+            }
+            KEY_TAB if ctrl => {
+                // switch to the next mode, or the previous one with shift
+                let count = state.modes.len();
+                state.mode_index = if shift {
+                    (state.mode_index + count - 1) % count
+                } else {
+                    (state.mode_index + 1) % count
+                };
+                state.last_text = None;
             }
+            // don't complete paths in passwords
+            KEY_TAB if state.active_mode().0 == Mode::Password => {}
             KEY_TAB => {
+                if complete_path(state) {
+                    return Action::Run;
+                }
+                if enter_selected_directory(state, sources) {
+                    return Action::Run;
+                }
                 if !state.suggestions.is_empty() {
-                    state.text = state.suggestions[state.selected as usize].1.to_string();
+                    state.text = state.suggestions[state.selected].name.to_string();
                     state.caret_pos = state.text.len() as i32;
                     state.selected = 0;
                 }
@@ -319,9 +1558,15 @@ fn handle_event(
                 // some other key
                 // try to interpret the key as a character
                 let c = xc.keyevent_to_char(e);
-                if !c.is_ascii_control() {
+                if c == '/' && enter_selected_directory(state, sources) {
+                    return Action::Run;
+                }
+                // a password mustn't outgrow its buffer
+                let full = state.active_mode().0 == Mode::Password
+                    && state.text.len() + c.len_utf8() > state.text.capacity();
+                if !c.is_ascii_control() && !full {
                     state.text.insert(state.caret_pos as usize, c);
-                    state.caret_pos += 1;
+                    state.caret_pos += c.len_utf8() as i32;
                     state.selected = 0;
                 }
             }
@@ -330,10 +1575,312 @@ fn handle_event(
     Action::Run
 }
 
+/// Does what the suggestion is for, returning whether the bar stays open
+fn choose(
+    xc: &X11Context,
+    suggestion: &Suggestion,
+    state: &mut State,
+    apps: &Mutex<applications::Apps>,
+    sources: &mut Sources,
+    args: &Args,
+    shift: bool,
+) -> Action {
+    match suggestion.target {
+        Target::App => launch_app(xc, apps, &suggestion.name, sources, args, shift),
+        Target::Command => {
+            // keep the arguments typed after the command
+            let query = state.active_mode().1.trim_start();
+            let arguments = query.find(' ').map(|i| &query[i..]).unwrap_or("");
+            run_command(&format!("{}{}", suggestion.name, arguments));
+        }
+        Target::Window(window) => xc.activate_window(window),
+        Target::Workspace(desktop) => {
+            // with shift, send the previously focused window there instead
+            if shift {
+                if let Some(window) = sources.focused_window {
+                    xc.move_window_to_desktop(window, desktop);
+                }
+            } else {
+                xc.switch_to_desktop(desktop);
+            }
+        }
+        Target::Copy => state.copy(&suggestion.name, "\n"),
+        Target::Confirm(confirmed) => state.cancelled = !confirmed,
+        Target::Character(characters) => {
+            if shift {
+                state
+                    .typed
+                    .get_or_insert_with(String::new)
+                    .push_str(characters);
+            } else {
+                state.copy(characters, "");
+            }
+        }
+        Target::File(ref path) => open_path(path, apps, sources, state, args),
+        Target::OpenWith(ref path) => open_with(apps, &suggestion.name, path, args),
+        Target::RecentFile(i) => {
+            // preferably with the application that opened it last
+            let file = &sources.recent_files[i];
+            match file.command() {
+                Some(command) => run_command(&command),
+                None => open_path(&file.path, apps, sources, state, args),
+            }
+        }
+        // with shift, the process is killed instead
+        Target::Process(pid) => processes::kill(pid, shift),
+        Target::Shell(ref command) => {
+            // with shift, in the terminal
+            let terminal = split_command(&args.terminal);
+            run_in_shell(command, Some(&terminal[..]).filter(|_| shift));
+        }
+        Target::Run(ref command) => run_command(command),
+        Target::Url(ref url) => open_url(url, args.browser.as_deref()),
+        Target::Mail(ref url) => open_file(Path::new(url)),
+        Target::Script(i) => {
+            let script = sources.script.as_mut().unwrap();
+            if !script.entries.get(i).is_some_and(|entry| entry.selectable) {
+                return Action::Run;
+            }
+            // keep the bar open if the script gave a new list of entries
+            if script.select(Some(i), "") {
+                state.reset_text();
+                return Action::Run;
+            }
+        }
+        Target::Provider(i, ref item) => {
+            sources.providers[i].activate(item);
+            match &item.action {
+                ProviderAction::None => {}
+                ProviderAction::Run(command) => run_command(command),
+                ProviderAction::Open(target) => open_file(Path::new(target)),
+                ProviderAction::Copy(text) => state.copy(text, "\n"),
+            }
+        }
+        Target::Ssh(ref host) => {
+            record_host(&host.display_name());
+            run_command(&format!(
+                "{} -e ssh {}",
+                args.terminal,
+                host.ssh_arguments()
+            ));
+        }
+    }
+    Action::Stop
+}
+
+/// The byte position of the character before the one at the position, the caret is kept at
+/// character boundaries
+fn prev_char_boundary(text: &str, position: usize) -> usize {
+    text[..position]
+        .char_indices()
+        .next_back()
+        .map_or(0, |(i, _)| i)
+}
+
+/// The byte position of the character after the one at the position
+fn next_char_boundary(text: &str, position: usize) -> usize {
+    text[position..]
+        .chars()
+        .next()
+        .map_or(position, |c| position + c.len_utf8())
+}
+
+/// The suggestion to open the text with the default application,
+/// if it's a URL, the path of a file or an email address.
+/// Executable files are run, with opening them offered below that.
+fn input_suggestions(query: &str) -> Vec<Suggestion> {
+    let open = |name, target| Suggestion {
+        score: i64::MAX,
+        name,
+        comment: "Open with the default application".to_owned(),
+        target,
+        mode: None,
+    };
+    match classify(query) {
+        Some(Input::Url(url)) => vec![open(format!("Open URL {}", url), Target::Url(url))],
+        Some(Input::Path(path)) => vec![open(
+            format!("Open file {}", path.display()),
+            Target::File(path),
+        )],
+        Some(Input::Mail(url)) => vec![open(
+            format!("Compose mail to {}", url.trim_start_matches("mailto:")),
+            Target::Mail(url),
+        )],
+        Some(Input::Executable(path)) => vec![
+            Suggestion {
+                score: i64::MAX,
+                name: format!("Run {}", path.display()),
+                comment: String::new(),
+                target: Target::Run(query.trim().to_owned()),
+                mode: None,
+            },
+            open(format!("Open file {}", path.display()), Target::File(path)),
+        ],
+        None => Vec::new(),
+    }
+}
+
+/// Launches the application, or switches to its window if it's already running
+/// and that's preferred. A new instance can be forced.
+fn launch_app(
+    xc: &X11Context,
+    apps: &Mutex<applications::Apps>,
+    name: &str,
+    sources: &Sources,
+    args: &Args,
+    force_new: bool,
+) {
+    let apps_lock = apps.lock().unwrap();
+    let app = &apps_lock.get(name).unwrap();
+    // a new instance can't be forced if the application can only have one window
+    let raise = (args.raise && !force_new) || app.single_main_window;
+    let running = find_app_window(&sources.windows, app);
+    if let Some(window) = running.filter(|_| raise) {
+        xc.activate_window(window.id);
+    } else if app.show_terminal {
+        run_command(&format!("{} -e \"{}\"", args.terminal, app.exec));
+    } else {
+        run_command(&app.exec);
+    }
+}
+
+/// Opens the file with the default application for its type.
+/// Until all applications are read the default isn't known, so xdg-open chooses it instead.
+fn open_path(
+    path: &Path,
+    apps: &Mutex<applications::Apps>,
+    sources: &Sources,
+    state: &State,
+    args: &Args,
+) {
+    if state.progress_finished.is_none() {
+        open_file(path);
+        return;
+    }
+    let (database, associations) = sources.mime();
+    let mime = database.mime_type(path);
+    let apps_lock = apps.lock().unwrap();
+    let default = applications_for(database, associations, &apps_lock, &mime)
+        .into_iter()
+        .next();
+    drop(apps_lock);
+    match default {
+        Some(name) => open_with(apps, &name, path, args),
+        None => open_file(path),
+    }
+}
+
+/// Opens the file with the application
+fn open_with(apps: &Mutex<applications::Apps>, name: &str, path: &Path, args: &Args) {
+    let apps_lock = apps.lock().unwrap();
+    let app = &apps_lock[name];
+    let command = app.command_with_file(path);
+    let mut arguments = split_command(&command);
+    if app.show_terminal {
+        // the command's arguments are passed on as they are, not split again
+        let mut terminal = split_command(&args.terminal);
+        terminal.push("-e".to_owned());
+        terminal.append(&mut arguments);
+        arguments = terminal;
+    }
+    if let Some((program, arguments)) = arguments.split_first() {
+        let _ = Command::new(program).args(arguments).spawn();
+    }
+}
+
+/// Whether the suggestions are for the same thing, even if they were found with different text
+fn same_suggestion(a: &Suggestion, b: &Suggestion) -> bool {
+    a.name == b.name && discriminant(&a.target) == discriminant(&b.target)
+}
+
+/// Descends into the selected directory in the file browser,
+/// returns false if the selected suggestion isn't a directory
+fn enter_selected_directory(state: &mut State, sources: &mut Sources) -> bool {
+    let browser = match &mut sources.files {
+        Some(browser) => browser,
+        None => return false,
+    };
+    match state.suggestions.get(state.selected).map(|s| &s.target) {
+        // located, recent and typed directories aren't always in the browsed one
+        Some(Target::File(path)) if path.is_dir() && path.parent() == Some(&browser.directory) => {
+            if let Some(name) = path.file_name() {
+                browser.enter(&name.to_string_lossy());
+            }
+            state.reset_text();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Completes the path-like word before the caret, or cycles to the next completion
+/// if already completing. Returns false if there is nothing to complete.
+fn complete_path(state: &mut State) -> bool {
+    let previous_len = match &mut state.completion {
+        Some(completion) => {
+            let len = completion.current().len();
+            completion.next();
+            len
+        }
+        None => {
+            state.completion = PathCompletion::new(&state.text, state.caret_pos as usize);
+            // nothing typed was replaced yet, so the word up to the caret is replaced
+            match &state.completion {
+                Some(completion) => state.caret_pos as usize - completion.start,
+                None => return false,
+            }
+        }
+    };
+    let completion = state.completion.as_ref().unwrap();
+    let start = completion.start;
+    state
+        .text
+        .replace_range(start..start + previous_len, completion.current());
+    state.caret_pos = (start + completion.current().len()) as i32;
+    true
+}
+
+/// Prints the password to stdout and overwrites it in memory
+fn print_password(password: &mut String) {
+    let mut stdout = io::stdout();
+    if let Err(e) = stdout
+        .write_all(password.as_bytes())
+        .and_then(|_| stdout.write_all(b"\n"))
+        .and_then(|_| stdout.flush())
+    {
+        eprintln!("Couldn't print the password: {}", e);
+    }
+    zero_text(password);
+}
+
+/// Removes the bytes in the range in place, and zeroes the bytes left behind after the end
+/// of the text, so that no copies of a password stay in memory
+fn remove_text(text: &mut String, range: Range<usize>) {
+    let len = text.len();
+    // draining doesn't reallocate
+    let bytes = unsafe { text.as_mut_vec() };
+    bytes.drain(range);
+    let removed = len - bytes.len();
+    for byte in &mut bytes.spare_capacity_mut()[..removed] {
+        // volatile, so that the writes aren't optimized away
+        unsafe { std::ptr::write_volatile(byte.as_mut_ptr(), 0) };
+    }
+}
+
+/// Overwrites the text with zeroes before clearing it, so that it doesn't stay in memory
+fn zero_text(text: &mut String) {
+    // zeroes are valid UTF-8, so the string stays valid
+    for byte in unsafe { text.as_bytes_mut() } {
+        // volatile, so that the writes aren't optimized away
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    text.clear();
+}
+
 fn run_command(command: &str) {
-    let mut parts = command.split(' ');
-    if !command.is_empty() {
-        let mut c = Command::new(parts.next().unwrap());
+    let mut parts = split_command(command).into_iter();
+    if let Some(program) = parts.next() {
+        let mut c = Command::new(program);
         c.args(parts);
         let _ = c.spawn();
     }
@@ -0,0 +1,773 @@
+use crate::applications::Apps;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env::var;
+use std::fs::{read, read_to_string, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The files' contents are never read further than this for magic
+const MAX_MAGIC_LENGTH: usize = 65536;
+
+/// The types of files, read from the shared-mime-info database
+#[derive(Debug, Default)]
+pub struct MimeDatabase {
+    globs: Vec<Glob>,
+    magic: Vec<Magic>,
+    /// Alias to the canonical type
+    aliases: HashMap<String, String>,
+    /// Type to the types it's a subclass of
+    parents: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+struct Glob {
+    weight: u32,
+    mime: String,
+    pattern: String,
+    /// None if the glob is case-sensitive
+    lowercase_pattern: Option<String>,
+}
+
+#[derive(Debug)]
+struct Magic {
+    priority: u32,
+    mime: String,
+    rules: Vec<MagicRule>,
+}
+
+/// The rules with a greater indent are only checked if the previous rule with a smaller one matched
+#[derive(Debug)]
+struct MagicRule {
+    indent: u32,
+    offset: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    /// How many offsets, starting with the first one, the value is looked for at
+    range: usize,
+}
+
+impl MimeDatabase {
+    /// Reads the database from the mime directories in $XDG_DATA_HOME and $XDG_DATA_DIRS
+    pub fn load() -> Self {
+        let mut database = Self::default();
+        // the more important directories come first, so their globs win when the weights are equal
+        for dir in data_dirs() {
+            let dir = dir.join("mime");
+            if let Ok(contents) = read_to_string(dir.join("globs2")) {
+                database.read_globs(&contents);
+            }
+            if let Ok(contents) = read(dir.join("magic")) {
+                database.read_magic(&contents);
+            }
+            if let Ok(contents) = read_to_string(dir.join("aliases")) {
+                for (alias, mime) in contents.lines().filter_map(split_pair) {
+                    database.aliases.entry(alias).or_insert(mime);
+                }
+            }
+            if let Ok(contents) = read_to_string(dir.join("subclasses")) {
+                for (mime, parent) in contents.lines().filter_map(split_pair) {
+                    let parents = database.parents.entry(mime).or_default();
+                    if !parents.contains(&parent) {
+                        parents.push(parent);
+                    }
+                }
+            }
+        }
+        // the most important magic is checked first
+        database.magic.sort_by_key(|magic| Reverse(magic.priority));
+        database
+    }
+
+    /// Lines of `weight:type:glob`, optionally followed by `:cs` for case-sensitive globs
+    fn read_globs(&mut self, contents: &str) {
+        for line in contents.lines().filter(|l| !l.starts_with('#')) {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            let weight = match fields[0].parse() {
+                Ok(weight) => weight,
+                Err(_) => continue,
+            };
+            let case_sensitive = fields.get(3).is_some_and(|flags| flags.contains("cs"));
+            self.globs.push(Glob {
+                weight,
+                mime: fields[1].to_owned(),
+                pattern: fields[2].to_owned(),
+                lowercase_pattern: if case_sensitive {
+                    None
+                } else {
+                    Some(fields[2].to_lowercase())
+                },
+            });
+        }
+    }
+
+    /// The binary format is `[priority:type]` sections, each followed by lines of
+    /// `[indent]>offset=<length><value>[&mask][~word size][+range]`
+    fn read_magic(&mut self, contents: &[u8]) {
+        let mut parser = Parser {
+            bytes: contents,
+            position: 0,
+        };
+        if !parser.take(b"MIME-Magic\0\n") {
+            return;
+        }
+        while !parser.done() {
+            if parser.take(b"[") {
+                let priority = parser.number().unwrap_or(50);
+                parser.take(b":");
+                let mime = parser.until(b']');
+                parser.skip_line();
+                self.magic.push(Magic {
+                    priority: priority as u32,
+                    mime: String::from_utf8_lossy(mime).into_owned(),
+                    rules: Vec::new(),
+                });
+                continue;
+            }
+            let rule = parser.magic_rule();
+            match (rule, self.magic.last_mut()) {
+                (Some(rule), Some(magic)) => magic.rules.push(rule),
+                // unknown lines are skipped
+                _ => parser.skip_line(),
+            }
+        }
+    }
+
+    /// Finds the type of the file from its name, or from its contents if the name isn't enough
+    pub fn mime_type(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return "inode/directory".to_owned();
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let candidates = self.glob_matches(&name);
+        if candidates.len() == 1 {
+            return candidates[0].to_owned();
+        }
+
+        let contents = read_start(path, self.magic_length());
+        // when several globs match, the contents decide between them
+        let magic = self.magic.iter().find(|magic| {
+            (candidates.is_empty() || candidates.contains(&magic.mime.as_str()))
+                && magic_matches(&magic.rules, 0, 0, &contents)
+        });
+        if let Some(magic) = magic {
+            return magic.mime.clone();
+        }
+        if let Some(mime) = candidates.first() {
+            return (*mime).to_owned();
+        }
+        if contents.contains(&0) {
+            "application/octet-stream".to_owned()
+        } else {
+            "text/plain".to_owned()
+        }
+    }
+
+    /// The types whose globs with the greatest weight match the name,
+    /// preferring the longest patterns
+    fn glob_matches(&self, name: &str) -> Vec<&str> {
+        // a glob with the same case as the name wins over one that only matches ignoring the case
+        let matches = self.best_globs(|glob| glob_match(glob.pattern.as_bytes(), name.as_bytes()));
+        if !matches.is_empty() {
+            return matches;
+        }
+        let lowercase = name.to_lowercase();
+        self.best_globs(|glob| match &glob.lowercase_pattern {
+            Some(pattern) => glob_match(pattern.as_bytes(), lowercase.as_bytes()),
+            None => false,
+        })
+    }
+
+    fn best_globs(&self, matches: impl Fn(&Glob) -> bool) -> Vec<&str> {
+        let mut best = (0, 0);
+        let mut types = Vec::new();
+        for glob in self.globs.iter().filter(|glob| matches(glob)) {
+            let rank = (glob.weight, glob.pattern.len());
+            if rank > best {
+                best = rank;
+                types.clear();
+            }
+            if rank == best && !types.contains(&glob.mime.as_str()) {
+                types.push(glob.mime.as_str());
+            }
+        }
+        types
+    }
+
+    /// How much of a file has to be read to check all the magic
+    fn magic_length(&self) -> usize {
+        let length = self
+            .magic
+            .iter()
+            .flat_map(|magic| &magic.rules)
+            .map(|rule| rule.offset + rule.range + rule.value.len())
+            .max()
+            .unwrap_or(0);
+        length.min(MAX_MAGIC_LENGTH)
+    }
+
+    /// The type itself if it's not an alias, or the type it's an alias for
+    pub fn canonical<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map_or(mime, |m| m.as_str())
+    }
+
+    /// The type followed by every type it's a subclass of, closest first
+    pub fn with_parents(&self, mime: &str) -> Vec<String> {
+        let mut types = vec![self.canonical(mime).to_owned()];
+        let mut i = 0;
+        while i < types.len() {
+            let mut parents = self.parents.get(&types[i]).cloned().unwrap_or_default();
+            // every text format can be read as plain text
+            if types[i].starts_with("text/") {
+                parents.push("text/plain".to_owned());
+            }
+            for parent in parents {
+                if !types.contains(&parent) {
+                    types.push(parent);
+                }
+            }
+            i += 1;
+        }
+        types
+    }
+}
+
+/// Which applications open which types, read from the mimeapps.list files
+#[derive(Debug, Default)]
+pub struct Associations {
+    /// The default applications for every type, the most important first
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    /// Removed from the applications that say they can open the type
+    removed: HashMap<String, Vec<String>>,
+}
+
+impl Associations {
+    /// Reads the mimeapps.list files in the order of importance given by the specification,
+    /// with the desktop-specific ones before the others in every directory
+    pub fn load(database: &MimeDatabase) -> Self {
+        let mut associations = Self::default();
+        let desktops: Vec<String> = var("XDG_CURRENT_DESKTOP")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(|d| d.to_lowercase())
+            .collect();
+        let mut dirs = config_dirs();
+        dirs.extend(data_dirs().into_iter().map(|dir| dir.join("applications")));
+        for dir in dirs {
+            for desktop in &desktops {
+                associations.read(&dir.join(format!("{}-mimeapps.list", desktop)), database);
+            }
+            associations.read(&dir.join("mimeapps.list"), database);
+        }
+        associations
+    }
+
+    fn read(&mut self, path: &Path, database: &MimeDatabase) {
+        let contents = match read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+        let mut removed_here = Vec::new();
+        let mut section = "";
+        for line in contents.lines().map(|l| l.trim()) {
+            if line.starts_with('[') {
+                section = line;
+                continue;
+            }
+            let (mime, ids) = match line.find('=') {
+                Some(i) => (database.canonical(line[..i].trim()), &line[i + 1..]),
+                None => continue,
+            };
+            let ids = ids
+                .split(';')
+                .map(|id| id.trim())
+                .filter(|id| !id.is_empty());
+            match section {
+                "[Default Applications]" => {
+                    self.defaults
+                        .entry(mime.to_owned())
+                        .or_default()
+                        .extend(ids.map(|id| id.to_owned()));
+                }
+                "[Added Associations]" => {
+                    // a more important file could have removed them
+                    let ids: Vec<String> = ids
+                        .filter(|id| !self.is_removed(mime, id))
+                        .map(|id| id.to_owned())
+                        .collect();
+                    self.added.entry(mime.to_owned()).or_default().extend(ids);
+                }
+                "[Removed Associations]" => {
+                    removed_here.extend(ids.map(|id| (mime.to_owned(), id.to_owned())));
+                }
+                _ => {}
+            }
+        }
+        // the associations are only removed from the less important files
+        for (mime, id) in removed_here {
+            self.removed.entry(mime).or_default().push(id);
+        }
+    }
+
+    fn is_removed(&self, mime: &str, id: &str) -> bool {
+        self.removed
+            .get(mime)
+            .is_some_and(|ids| ids.iter().any(|i| i == id))
+    }
+}
+
+/// The names of the applications that can open the type, the default one first
+pub fn applications_for(
+    database: &MimeDatabase,
+    associations: &Associations,
+    apps: &Apps,
+    mime: &str,
+) -> Vec<String> {
+    let names: HashMap<&str, &str> = apps
+        .iter()
+        .filter(|(_, app)| !app.id.is_empty())
+        .map(|(name, app)| (app.id.as_str(), name.as_str()))
+        .collect();
+
+    let mut ids: Vec<&str> = Vec::new();
+    for mime in database.with_parents(mime) {
+        // only the first default that is installed counts
+        let default = associations
+            .defaults
+            .get(&mime)
+            .and_then(|ids| ids.iter().find(|id| names.contains_key(id.as_str())));
+        ids.extend(default.map(|id| id.as_str()));
+        if let Some(added) = associations.added.get(&mime) {
+            ids.extend(added.iter().map(|id| id.as_str()));
+        }
+        for app in apps.values() {
+            let supported = app
+                .mime_types
+                .iter()
+                .any(|m| database.canonical(m) == mime || wildcard_match(m, &mime));
+            if supported && !associations.is_removed(&mime, &app.id) {
+                ids.push(&app.id);
+            }
+        }
+    }
+
+    let mut applications: Vec<String> = Vec::new();
+    for id in ids {
+        if let Some(name) = names.get(id) {
+            if !applications.iter().any(|a| a == name) {
+                applications.push((*name).to_owned());
+            }
+        }
+    }
+    applications
+}
+
+/// `image/*` matches every image type
+fn wildcard_match(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(media) => mime.split('/').next() == Some(media),
+        None => false,
+    }
+}
+
+/// $XDG_DATA_HOME and then $XDG_DATA_DIRS
+fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => dirs.push(PathBuf::from(dir)),
+        _ => {
+            if let Ok(home) = var("HOME") {
+                dirs.push(Path::new(&home).join(".local/share"));
+            }
+        }
+    }
+    let data_dirs = match var("XDG_DATA_DIRS") {
+        Ok(d) if !d.is_empty() => d,
+        _ => "/usr/local/share/:/usr/share/".to_owned(),
+    };
+    dirs.extend(data_dirs.split(':').map(PathBuf::from));
+    dirs
+}
+
+/// $XDG_CONFIG_HOME and then $XDG_CONFIG_DIRS
+fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => dirs.push(PathBuf::from(dir)),
+        _ => {
+            if let Ok(home) = var("HOME") {
+                dirs.push(Path::new(&home).join(".config"));
+            }
+        }
+    }
+    let config_dirs = match var("XDG_CONFIG_DIRS") {
+        Ok(d) if !d.is_empty() => d,
+        _ => "/etc/xdg".to_owned(),
+    };
+    dirs.extend(config_dirs.split(':').map(PathBuf::from));
+    dirs
+}
+
+fn split_pair(line: &str) -> Option<(String, String)> {
+    let mut words = line.split_whitespace();
+    Some((words.next()?.to_owned(), words.next()?.to_owned()))
+}
+
+fn read_start(path: &Path, length: usize) -> Vec<u8> {
+    let mut contents = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(length as u64).read_to_end(&mut contents);
+    }
+    contents
+}
+
+/// Whether any rule with the indent, starting at the index, matches along with one of its children
+fn magic_matches(rules: &[MagicRule], start: usize, indent: u32, contents: &[u8]) -> bool {
+    for (i, rule) in rules.iter().enumerate().skip(start) {
+        if rule.indent < indent {
+            break;
+        }
+        if rule.indent > indent || !rule_matches(rule, contents) {
+            continue;
+        }
+        let has_children = rules.get(i + 1).is_some_and(|r| r.indent > indent);
+        if !has_children || magic_matches(rules, i + 1, indent + 1, contents) {
+            return true;
+        }
+    }
+    false
+}
+
+fn rule_matches(rule: &MagicRule, contents: &[u8]) -> bool {
+    (rule.offset..rule.offset + rule.range).any(|offset| {
+        let data = match contents.get(offset..offset + rule.value.len()) {
+            Some(data) => data,
+            None => return false,
+        };
+        match &rule.mask {
+            Some(mask) => data
+                .iter()
+                .zip(&rule.value)
+                .zip(mask)
+                .all(|((d, v), m)| d & m == v & m),
+            None => data == rule.value.as_slice(),
+        }
+    })
+}
+
+/// Matches `*`, `?` and `[...]` like fnmatch
+pub fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(b'*') => (0..=name.len()).any(|i| glob_match(&pattern[1..], &name[i..])),
+        Some(b'?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some(b'[') => {
+            let end = match pattern.iter().skip(2).position(|&c| c == b']') {
+                Some(i) => i + 2,
+                None => {
+                    return name.first() == Some(&b'[') && glob_match(&pattern[1..], &name[1..])
+                }
+            };
+            let (negated, set) = match pattern[1] {
+                b'!' | b'^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end]),
+            };
+            let c = match name.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    found |= set[i] <= c && c <= set[i + 2];
+                    i += 3;
+                } else {
+                    found |= set[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && glob_match(&pattern[end + 1..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Reads the magic file
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn done(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+    fn take(&mut self, expected: &[u8]) -> bool {
+        if self.bytes[self.position..].starts_with(expected) {
+            self.position += expected.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn number(&mut self) -> Option<usize> {
+        let digits = self.bytes[self.position..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let number = std::str::from_utf8(&self.bytes[self.position..self.position + digits])
+            .ok()?
+            .parse()
+            .ok();
+        self.position += digits;
+        number
+    }
+    fn until(&mut self, end: u8) -> &'a [u8] {
+        let length = self.bytes[self.position..]
+            .iter()
+            .position(|&c| c == end)
+            .unwrap_or(self.bytes.len() - self.position);
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        bytes
+    }
+    fn skip_line(&mut self) {
+        self.until(b'\n');
+        self.position += 1;
+    }
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(bytes)
+    }
+    fn magic_rule(&mut self) -> Option<MagicRule> {
+        let indent = self.number().unwrap_or(0) as u32;
+        if !self.take(b">") {
+            return None;
+        }
+        let offset = self.number()?;
+        if !self.take(b"=") {
+            return None;
+        }
+        let length = self.bytes(2)?;
+        let length = (length[0] as usize) << 8 | length[1] as usize;
+        let mut value = self.bytes(length)?.to_vec();
+        let mut mask = None;
+        if self.take(b"&") {
+            mask = Some(self.bytes(length)?.to_vec());
+        }
+        let mut word_size = 1;
+        if self.take(b"~") {
+            word_size = self.number()?;
+        }
+        let mut range = 1;
+        if self.take(b"+") {
+            range = self.number()?;
+        }
+        if !self.take(b"\n") {
+            return None;
+        }
+        // the values are big-endian words, a word size of 0 or 1 leaves them as they are
+        if cfg!(target_endian = "little") && word_size > 1 {
+            for word in value.chunks_mut(word_size) {
+                word.reverse();
+            }
+            if let Some(mask) = &mut mask {
+                for word in mask.chunks_mut(word_size) {
+                    word.reverse();
+                }
+            }
+        }
+        Some(MagicRule {
+            indent,
+            offset,
+            value,
+            mask,
+            range: range.max(1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::applications::App;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::process;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match(b"*.txt", b"notes.txt"));
+        assert!(!glob_match(b"*.txt", b"notes.txt~"));
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"README*", b"README.md"));
+        assert!(glob_match(b"?akefile", b"Makefile"));
+        assert!(!glob_match(b"?akefile", b"akefile"));
+        assert!(glob_match(b"*.[ch]", b"main.c"));
+        assert!(!glob_match(b"*.[ch]", b"main.o"));
+        assert!(glob_match(b"*.[a-c]x", b"f.bx"));
+        assert!(glob_match(b"*.[!a-c]", b"f.d"));
+        assert!(!glob_match(b"*.[^a-c]", b"f.a"));
+        // an unclosed bracket is matched literally
+        assert!(glob_match(b"[a", b"[a"));
+        assert!(!glob_match(b"[a", b"a"));
+    }
+
+    #[test]
+    fn glob_weights_and_case() {
+        let mut database = MimeDatabase::default();
+        database.read_globs(
+            "# comment\n\
+             50:text/x-c:*.c:cs\n\
+             50:text/x-c++:*.C:cs\n\
+             50:application/gzip:*.gz\n\
+             50:application/x-compressed-tar:*.tar.gz\n\
+             80:text/x-makefile:makefile\n\
+             40:text/plain:*.txt\n\
+             invalid line\n",
+        );
+        assert_eq!(database.glob_matches("a.c"), vec!["text/x-c"]);
+        assert_eq!(database.glob_matches("a.C"), vec!["text/x-c++"]);
+        // the longest pattern wins
+        assert_eq!(
+            database.glob_matches("a.tar.gz"),
+            vec!["application/x-compressed-tar"]
+        );
+        // case-insensitive globs match any case, after the ones with the same case
+        assert_eq!(database.glob_matches("NOTES.TXT"), vec!["text/plain"]);
+        assert_eq!(database.glob_matches("Makefile"), vec!["text/x-makefile"]);
+        assert!(database.glob_matches("a.h").is_empty());
+    }
+
+    #[test]
+    fn magic() {
+        let mut database = MimeDatabase::default();
+        database.read_magic(
+            b"MIME-Magic\0\n\
+              [50:image/png]\n\
+              >0=\x00\x04\x89PNG\n\
+              [80:application/x-test]\n\
+              >0=\x00\x02AB~0\n\
+              1>4=\x00\x02CD+2\n\
+              1>0=\x00\x02\x12\x34&\xff\x0f~2\n\
+              unknown line\n\
+              >0=\x00\x01Z~1\n",
+        );
+        database.magic.sort_by_key(|magic| Reverse(magic.priority));
+        assert_eq!(database.magic.len(), 2);
+        let test = &database.magic[0];
+        assert_eq!(test.mime, "application/x-test");
+        assert_eq!(test.rules.len(), 4);
+        assert_eq!(test.rules[1].indent, 1);
+        assert_eq!(test.rules[1].range, 2);
+        // words are swapped to the byte order of the machine
+        if cfg!(target_endian = "little") {
+            assert_eq!(test.rules[2].value, vec![0x34, 0x12]);
+            assert_eq!(test.rules[2].mask, Some(vec![0x0f, 0xff]));
+        }
+        assert_eq!(test.rules[3].value, b"Z");
+
+        let matches = |contents: &[u8]| magic_matches(&test.rules, 0, 0, contents);
+        // the children are checked after their parent matched
+        assert!(matches(b"AB__CD"));
+        assert!(matches(b"AB___CD"));
+        assert!(!matches(b"AB____CD"));
+        assert!(!matches(b"XX__CD"));
+        // without a matching child only the rule without children does
+        assert!(!matches(b"AB"));
+        assert!(matches(b"Z"));
+        assert!(magic_matches(
+            &database.magic[1].rules,
+            0,
+            0,
+            b"\x89PNG\r\n"
+        ));
+        // a truncated file is no magic at all
+        let mut truncated = MimeDatabase::default();
+        truncated.read_magic(b"MIME-Magic\0\n[50:image/png]\n>0=\x00\x09\x89P");
+        assert!(truncated.magic[0].rules.is_empty());
+    }
+
+    fn app(id: &str, mime_types: &[&str]) -> App {
+        App {
+            exec: String::new(),
+            comment: String::new(),
+            show_terminal: false,
+            wm_class: String::new(),
+            single_main_window: false,
+            id: id.to_owned(),
+            mime_types: mime_types.iter().map(|m| (*m).to_owned()).collect(),
+            exec_template: String::new(),
+        }
+    }
+
+    #[test]
+    fn mimeapps_precedence() {
+        let dir = temp_dir().join(format!("rlaunch-mimeapps-{}", process::id()));
+        create_dir_all(&dir).unwrap();
+        let user = dir.join("user.list");
+        let system = dir.join("system.list");
+        write(
+            &user,
+            "[Default Applications]\n\
+             text/plain=missing.desktop;editor.desktop\n\
+             [Removed Associations]\n\
+             text/plain=viewer.desktop;\n",
+        )
+        .unwrap();
+        write(
+            &system,
+            "[Default Applications]\n\
+             text/plain=viewer.desktop\n\
+             text/markdown=viewer.desktop\n\
+             [Added Associations]\n\
+             text/plain=viewer.desktop;other.desktop;\n\
+             [Removed Associations]\n\
+             text/plain=editor.desktop\n",
+        )
+        .unwrap();
+        let mut database = MimeDatabase::default();
+        database.read_globs("50:text/markdown:*.md\n");
+        database
+            .aliases
+            .insert("text/x-markdown".to_owned(), "text/markdown".to_owned());
+        // the more important file first
+        let mut associations = Associations::default();
+        associations.read(&user, &database);
+        associations.read(&system, &database);
+        remove_dir_all(&dir).unwrap();
+
+        let mut apps = Apps::new();
+        apps.insert("Editor".to_owned(), app("editor.desktop", &["text/plain"]));
+        apps.insert("Viewer".to_owned(), app("viewer.desktop", &["text/*"]));
+        apps.insert("Other".to_owned(), app("other.desktop", &[]));
+        apps.insert("Images".to_owned(), app("images.desktop", &["image/png"]));
+        apps.insert(
+            "Notes".to_owned(),
+            app("notes.desktop", &["text/x-markdown"]),
+        );
+
+        // the first installed default, then the added ones, then the rest, without the
+        // ones removed by a more important file
+        assert_eq!(
+            applications_for(&database, &associations, &apps, "text/plain"),
+            vec!["Editor", "Other"]
+        );
+        // the less important file's default still counts for other types, and the
+        // applications of the parent types come after the type's own
+        assert_eq!(
+            applications_for(&database, &associations, &apps, "text/x-markdown"),
+            vec!["Viewer", "Notes", "Editor", "Other"]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use crate::zero_text;
+use std::env::set_var;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+/// GPG_ERR_CANCELED from the pinentry source
+const ERROR_CANCELLED: &str = "83886179 Operation cancelled <Pinentry>";
+/// GPG_ERR_NOT_CONFIRMED from the pinentry source
+const ERROR_NOT_CONFIRMED: &str = "83886194 Not confirmed <Pinentry>";
+/// GPG_ERR_ASS_UNKNOWN_CMD
+const ERROR_UNKNOWN_COMMAND: &str = "536871187 Unknown IPC command <User defined source 1>";
+
+/// What is shown to the user, as set by the client
+#[derive(Debug, Default, Clone)]
+pub struct Request {
+    pub description: Option<String>,
+    pub prompt: Option<String>,
+    /// Why the previously entered PIN was wrong, only shown once
+    pub error: Option<String>,
+    pub ok: Option<String>,
+    pub cancel: Option<String>,
+}
+
+/// Asks the user what the client wants to know
+pub trait Dialog {
+    /// Returns the entered PIN, or None if cancelled
+    fn get_pin(&mut self, request: &Request) -> Option<String>;
+    /// Returns whether the user confirmed. With one button the user can only acknowledge.
+    fn confirm(&mut self, request: &Request, one_button: bool) -> bool;
+}
+
+/// Speaks the Assuan protocol used by GnuPG with pinentries, until the client says BYE
+/// or closes the input
+pub fn serve(input: impl BufRead, mut output: impl Write, dialog: &mut impl Dialog) {
+    let mut request = Request::default();
+    if send(&mut output, "OK Pleased to meet you").is_err() {
+        return;
+    }
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        let (command, argument) = match line.find(' ') {
+            Some(i) => (line[..i].to_uppercase(), &line[i + 1..]),
+            None => (line.to_uppercase(), ""),
+        };
+        let result = match command.as_str() {
+            // comments and empty lines are ignored
+            "" => continue,
+            _ if command.starts_with('#') => continue,
+            "SETDESC" => set(&mut request.description, argument),
+            "SETPROMPT" => set(&mut request.prompt, argument),
+            "SETERROR" => set(&mut request.error, argument),
+            "SETOK" => set(&mut request.ok, argument),
+            "SETCANCEL" => set(&mut request.cancel, argument),
+            "OPTION" => {
+                // the display has to be set before the bar is shown for the first time
+                if let Some(display) = argument.strip_prefix("display=") {
+                    set_var("DISPLAY", display);
+                }
+                Ok(())
+            }
+            "GETINFO" => {
+                let info = match argument {
+                    "pid" => Some(process::id().to_string()),
+                    "version" => Some(env!("CARGO_PKG_VERSION").to_owned()),
+                    "flavor" => Some("rlaunch".to_owned()),
+                    _ => None,
+                };
+                if let Some(info) = info {
+                    let _ = send(&mut output, &data_line(&info));
+                }
+                Ok(())
+            }
+            "GETPIN" => {
+                let pin = dialog.get_pin(&request);
+                request.error = None;
+                match pin {
+                    Some(mut pin) => {
+                        // an empty PIN is sent without a data line
+                        if !pin.is_empty() {
+                            let mut line = data_line(&pin);
+                            let _ = send(&mut output, &line);
+                            zero_text(&mut line);
+                        }
+                        zero_text(&mut pin);
+                        Ok(())
+                    }
+                    None => Err(ERROR_CANCELLED),
+                }
+            }
+            "CONFIRM" | "MESSAGE" => {
+                let one_button = command == "MESSAGE" || argument == "--one-button";
+                let confirmed = dialog.confirm(&request, one_button);
+                request.error = None;
+                if confirmed || one_button {
+                    Ok(())
+                } else {
+                    Err(ERROR_NOT_CONFIRMED)
+                }
+            }
+            "RESET" => {
+                request = Request::default();
+                Ok(())
+            }
+            // accepted, but not shown
+            "NOP" | "SETTITLE" | "SETNOTOK" | "SETKEYINFO" | "SETTIMEOUT" | "SETQUALITYBAR"
+            | "SETQUALITYBAR_TT" | "SETREPEAT" | "SETREPEATERROR" | "SETGENPIN"
+            | "SETGENPIN_TT" => Ok(()),
+            "BYE" => {
+                let _ = send(&mut output, "OK closing connection");
+                return;
+            }
+            _ => Err(ERROR_UNKNOWN_COMMAND),
+        };
+        let response = match result {
+            Ok(()) => "OK".to_owned(),
+            Err(error) => format!("ERR {}", error),
+        };
+        if send(&mut output, &response).is_err() {
+            // the client is gone
+            return;
+        }
+    }
+}
+
+fn set(field: &mut Option<String>, argument: &str) -> Result<(), &'static str> {
+    *field = if argument.is_empty() {
+        None
+    } else {
+        Some(unescape(argument))
+    };
+    Ok(())
+}
+
+fn send(output: &mut impl Write, line: &str) -> io::Result<()> {
+    writeln!(output, "{}", line)?;
+    output.flush()
+}
+
+/// A `D` line with the text, escaping the characters that can't be sent in it as %XX.
+/// It's allocated only once, big enough for every character to be escaped, so that
+/// no copies of a PIN are left behind by reallocating.
+fn data_line(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() * 3 + 2);
+    escaped += "D ";
+    for c in text.chars() {
+        match c {
+            '%' => escaped += "%25",
+            '\r' => escaped += "%0D",
+            '\n' => escaped += "%0A",
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Decodes the %XX escapes in arguments, leaving out NULs, which can't be shown
+fn unescape(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut unescaped = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = text
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match code {
+            Some(code) if bytes[i] == b'%' => {
+                if code != 0 {
+                    unescaped.push(code);
+                }
+                i += 3;
+            }
+            _ => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers with the given PINs and confirmations, remembering what it was asked
+    #[derive(Default)]
+    struct MockDialog {
+        pins: Vec<Option<&'static str>>,
+        confirmations: Vec<bool>,
+        requests: Vec<(Request, Option<bool>)>,
+    }
+
+    impl Dialog for MockDialog {
+        fn get_pin(&mut self, request: &Request) -> Option<String> {
+            self.requests.push((request.clone(), None));
+            self.pins.remove(0).map(|pin| pin.to_owned())
+        }
+        fn confirm(&mut self, request: &Request, one_button: bool) -> bool {
+            self.requests.push((request.clone(), Some(one_button)));
+            self.confirmations.remove(0)
+        }
+    }
+
+    /// Serves the client's lines and returns the lines sent back
+    fn transcript(client: &str, dialog: &mut MockDialog) -> Vec<String> {
+        let mut output = Vec::new();
+        serve(client.as_bytes(), &mut output, dialog);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn get_pin() {
+        // as sent by gpg-agent, with an error after a wrong passphrase
+        let client = "\
+OPTION ttyname=/dev/pts/1
+SETDESC Please enter the passphrase to%0Aunlock the key 100%25
+SETPROMPT Passphrase:
+GETPIN
+SETERROR Bad Passphrase (try 2 of 3)
+GETPIN
+GETPIN
+BYE
+";
+        let mut dialog = MockDialog {
+            pins: vec![Some("wrong"), Some("p%ss\nword"), None],
+            ..Default::default()
+        };
+        assert_eq!(
+            transcript(client, &mut dialog),
+            vec![
+                "OK Pleased to meet you",
+                "OK",
+                "OK",
+                "OK",
+                "D wrong",
+                "OK",
+                "OK",
+                "D p%25ss%0Aword",
+                "OK",
+                "ERR 83886179 Operation cancelled <Pinentry>",
+                "OK closing connection",
+            ]
+        );
+        let (first, _) = &dialog.requests[0];
+        assert_eq!(
+            first.description.as_deref(),
+            Some("Please enter the passphrase to\nunlock the key 100%")
+        );
+        assert_eq!(first.prompt.as_deref(), Some("Passphrase:"));
+        assert_eq!(first.error, None);
+        // the error is only shown once
+        let (second, _) = &dialog.requests[1];
+        assert_eq!(second.error.as_deref(), Some("Bad Passphrase (try 2 of 3)"));
+        assert_eq!(dialog.requests[2].0.error, None);
+    }
+
+    #[test]
+    fn empty_pin() {
+        let mut dialog = MockDialog {
+            pins: vec![Some("")],
+            ..Default::default()
+        };
+        assert_eq!(
+            transcript("GETPIN\n", &mut dialog),
+            vec!["OK Pleased to meet you", "OK"]
+        );
+    }
+
+    #[test]
+    fn confirm_and_message() {
+        let client = "\
+SETOK %5FYes
+SETCANCEL %5FNo
+CONFIRM
+CONFIRM
+CONFIRM --one-button
+MESSAGE
+";
+        let mut dialog = MockDialog {
+            confirmations: vec![true, false, false, false],
+            ..Default::default()
+        };
+        assert_eq!(
+            transcript(client, &mut dialog),
+            vec![
+                "OK Pleased to meet you",
+                "OK",
+                "OK",
+                "OK",
+                "ERR 83886194 Not confirmed <Pinentry>",
+                // acknowledging can't be refused
+                "OK",
+                "OK",
+            ]
+        );
+        let one_button: Vec<_> = dialog.requests.iter().map(|(_, b)| b.unwrap()).collect();
+        assert_eq!(one_button, vec![false, false, true, true]);
+        assert_eq!(dialog.requests[0].0.ok.as_deref(), Some("_Yes"));
+        assert_eq!(dialog.requests[0].0.cancel.as_deref(), Some("_No"));
+    }
+
+    #[test]
+    fn other_commands() {
+        let client = "\
+# a comment
+
+SETTITLE Title
+SETKEYINFO n/ABCDEF
+GETINFO flavor
+GETINFO unknown
+SETDESC Description
+RESET
+FROBNICATE
+";
+        let mut dialog = MockDialog {
+            pins: vec![None],
+            ..Default::default()
+        };
+        assert_eq!(
+            transcript(&format!("{}GETPIN\n", client), &mut dialog),
+            vec![
+                "OK Pleased to meet you",
+                "OK",
+                "OK",
+                "D rlaunch",
+                "OK",
+                "OK",
+                "OK",
+                "OK",
+                "ERR 536871187 Unknown IPC command <User defined source 1>",
+                "ERR 83886179 Operation cancelled <Pinentry>",
+            ]
+        );
+        // everything set before RESET is forgotten
+        assert_eq!(dialog.requests[0].0.description, None);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape("a%25b%0a%0Dc%zz%2"), "a%b\n\rc%zz%2");
+        // NULs can't be shown on the bar
+        assert_eq!(unescape("a%00b"), "ab");
+        assert_eq!(data_line("100%\r\n"), "D 100%25%0D%0A");
+        // the worst case fits without reallocating
+        let line = data_line("%\n\r%");
+        assert_eq!(line, "D %25%0A%0D%25");
+        assert_eq!(line.capacity(), 4 * 3 + 2);
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs::{read, read_dir, read_to_string};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// How often the processes are reread while the bar is open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: i32,
+    pub name: String,
+    /// The arguments, separated by spaces
+    pub command: String,
+    /// The share of a CPU used since the previous refresh, in percent
+    pub cpu: f32,
+    /// The resident memory, in kB
+    pub rss: u64,
+}
+
+impl Process {
+    /// The name, the PID and the usage, as shown on the bar
+    pub fn description(&self) -> String {
+        format!(
+            "{}  {}  {:.1}%  {:.1} MiB",
+            self.name,
+            self.pid,
+            self.cpu,
+            self.rss as f32 / 1024.0
+        )
+    }
+}
+
+/// The current user's processes, using the most CPU and then memory first
+pub struct ProcessList {
+    pub processes: Vec<Process>,
+    last_refresh: Instant,
+    /// The CPU time of every process at the last refresh, in clock ticks
+    cpu_times: HashMap<i32, u64>,
+    clock_ticks: f32,
+}
+
+impl ProcessList {
+    pub fn new() -> Self {
+        let mut list = Self {
+            processes: Vec::new(),
+            last_refresh: Instant::now(),
+            cpu_times: HashMap::new(),
+            clock_ticks: unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f32,
+        };
+        list.read();
+        list
+    }
+    /// Rereads the processes if it's time to, returning whether it was
+    pub fn refresh(&mut self) -> bool {
+        if self.last_refresh.elapsed() < REFRESH_INTERVAL {
+            return false;
+        }
+        self.read();
+        true
+    }
+    fn read(&mut self) {
+        let elapsed = self.last_refresh.elapsed().as_secs_f32();
+        self.last_refresh = Instant::now();
+        let uid = unsafe { libc::getuid() };
+        let mut cpu_times = HashMap::new();
+        self.processes.clear();
+
+        let entries = match read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Couldn't read /proc: {}", e);
+                return;
+            }
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let pid: i32 = match entry.file_name().to_string_lossy().parse() {
+                Ok(pid) => pid,
+                Err(_) => continue,
+            };
+            // don't offer to kill rlaunch itself
+            if pid == process::id() as i32 {
+                continue;
+            }
+            // the process could have exited since it was listed
+            let status = match read_to_string(entry.path().join("status")) {
+                Ok(status) => status,
+                Err(_) => continue,
+            };
+            let mut name = String::new();
+            let mut owner = None;
+            let mut rss = 0;
+            for line in status.lines() {
+                if let Some(value) = line.strip_prefix("Name:") {
+                    name = value.trim().to_owned();
+                } else if let Some(value) = line.strip_prefix("Uid:") {
+                    // the real user ID comes first
+                    owner = value.split_whitespace().next().and_then(|u| u.parse().ok());
+                } else if let Some(value) = line.strip_prefix("VmRSS:") {
+                    rss = value
+                        .split_whitespace()
+                        .next()
+                        .and_then(|r| r.parse().ok())
+                        .unwrap_or(0);
+                }
+            }
+            if owner != Some(uid) {
+                continue;
+            }
+            let cpu_time = match read_to_string(entry.path().join("stat")) {
+                Ok(stat) => parse_cpu_time(&stat).unwrap_or(0),
+                Err(_) => continue,
+            };
+            let cpu = match self.cpu_times.get(&pid) {
+                Some(previous) if elapsed > 0.0 => {
+                    cpu_time.saturating_sub(*previous) as f32 / self.clock_ticks / elapsed * 100.0
+                }
+                // not known until the next refresh
+                _ => 0.0,
+            };
+            cpu_times.insert(pid, cpu_time);
+
+            let command = read(entry.path().join("cmdline")).unwrap_or_default();
+            let command = String::from_utf8_lossy(&command)
+                .split('\0')
+                .filter(|a| !a.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.processes.push(Process {
+                pid,
+                name,
+                command,
+                cpu,
+                rss,
+            });
+        }
+        self.cpu_times = cpu_times;
+        self.processes.sort_by(|a, b| {
+            b.cpu
+                .partial_cmp(&a.cpu)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.rss.cmp(&a.rss))
+        });
+    }
+}
+
+/// The user and system time from /proc/<pid>/stat, the 14th and 15th fields.
+/// The name in the second field is in parentheses and can contain spaces and parentheses.
+fn parse_cpu_time(stat: &str) -> Option<u64> {
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let user: u64 = fields.get(11)?.parse().ok()?;
+    let system: u64 = fields.get(12)?.parse().ok()?;
+    Some(user + system)
+}
+
+/// Asks the process to terminate, or kills it if forced
+pub fn kill(pid: i32, force: bool) {
+    let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        eprintln!("Couldn't kill {}: {}", pid, std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cpu_time;
+
+    #[test]
+    fn stat() {
+        // the name is "a) (b c)", which looks like the end of the name and more fields
+        let stat = "1234 (a) (b c)) S 1 1234 1234 0 -1 4194304 2512 0 3 0 157 42 0 0 20 0 1 0 \
+                    18446 12345678 900 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 3 0 0 0 0 0\n";
+        assert_eq!(parse_cpu_time(stat), Some(157 + 42));
+        assert_eq!(
+            parse_cpu_time("1 (init) S 0 1 1 0 -1 4194560 100 0 0 0 7 9"),
+            Some(16)
+        );
+        // cut short, or without the name
+        assert_eq!(
+            parse_cpu_time("1 (init) S 0 1 1 0 -1 4194560 100 0 0 0 7"),
+            None
+        );
+        assert_eq!(parse_cpu_time("1 init S"), None);
+    }
+}
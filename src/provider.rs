@@ -0,0 +1,347 @@
+use crate::completion::split_command;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub enum ProviderAction {
+    /// Only notify the provider
+    None,
+    Run(String),
+    Open(String),
+    Copy(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProviderItem {
+    pub id: String,
+    pub label: String,
+    pub comment: String,
+    pub score: i64,
+    pub action: ProviderAction,
+}
+
+/// The part shared with the thread reading the provider's output
+struct Results {
+    /// The query whose items are accepted, 0 if none
+    query_id: u64,
+    items: Vec<ProviderItem>,
+    done: bool,
+    /// Increased every time the items change
+    generation: u64,
+}
+
+/// A long-lived process giving results for the typed text, talking JSON lines over stdin/stdout.
+///
+/// rlaunch writes one JSON object per line to the provider's stdin:
+///  - `{"type":"query","id":1,"query":"text"}` whenever the typed text changes
+///  - `{"type":"cancel","id":1}` when the results of a query aren't needed anymore
+///    (a newer query was sent, or the provider took too long)
+///  - `{"type":"activate","query_id":1,"id":"item-id"}` when one of the items is chosen
+///
+/// and the provider answers with one JSON object per line on its stdout:
+///  - `{"type":"item","query_id":1,"id":"item-id","label":"Text","comment":"...","score":10,
+///    "action":{"type":"run","command":"..."}}`, where the action is optional and its type
+///    is either `run` (a command), `open` (a file or URL, given as `target`) or `copy` (`text`).
+///    Items with the same ID replace each other.
+///  - `{"type":"done","query_id":1}` when there are no more items for the query
+///
+/// Items for other queries than the last one are ignored.
+pub struct Provider {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    results: Arc<Mutex<Results>>,
+    last_query_id: u64,
+    sent_at: Instant,
+    timeout: Duration,
+}
+
+impl Provider {
+    pub fn spawn(command: &str, timeout: Duration) -> Option<Self> {
+        let command = split_command(command);
+        let (program, arguments) = command.split_first()?;
+        let mut child = match Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Couldn't start provider {:?}: {}", program, e);
+                return None;
+            }
+        };
+
+        let results = Arc::new(Mutex::new(Results {
+            query_id: 0,
+            items: Vec::new(),
+            done: true,
+            generation: 0,
+        }));
+        let stdout = child.stdout.take()?;
+        let results_clone = results.clone();
+        thread::spawn(move || read_results(BufReader::new(stdout), &results_clone));
+
+        Some(Self {
+            stdin: child.stdin.take(),
+            child,
+            results,
+            last_query_id: 0,
+            sent_at: Instant::now(),
+            timeout,
+        })
+    }
+    /// Sends a new query, cancelling the previous one if it's not done yet
+    pub fn query(&mut self, query: &str) {
+        let mut results = self.results.lock().unwrap();
+        let unfinished = if !results.done && results.query_id != 0 {
+            Some(results.query_id)
+        } else {
+            None
+        };
+        self.last_query_id += 1;
+        results.query_id = self.last_query_id;
+        results.items.clear();
+        results.done = false;
+        results.generation += 1;
+        drop(results);
+
+        if let Some(query_id) = unfinished {
+            self.send(&json!({"type": "cancel", "id": query_id}));
+        }
+        self.send(&json!({"type": "query", "id": self.last_query_id, "query": query}));
+        self.sent_at = Instant::now();
+    }
+    /// Stops waiting for the results of the current query if the provider takes too long.
+    /// Items that arrived before that are kept.
+    pub fn check_timeout(&mut self) {
+        let mut results = self.results.lock().unwrap();
+        if results.done || self.sent_at.elapsed() < self.timeout {
+            return;
+        }
+        let query_id = results.query_id;
+        results.query_id = 0;
+        results.done = true;
+        drop(results);
+        self.send(&json!({"type": "cancel", "id": query_id}));
+    }
+    /// Returns the items of the current query and a number that changes whenever they change
+    pub fn items(&self) -> (u64, Vec<ProviderItem>) {
+        let results = self.results.lock().unwrap();
+        (results.generation, results.items.clone())
+    }
+    pub fn activate(&mut self, item: &ProviderItem) {
+        self.send(&json!({
+            "type": "activate",
+            "query_id": self.last_query_id,
+            "id": item.id,
+        }));
+    }
+    fn send(&mut self, message: &Value) {
+        if let Some(stdin) = &mut self.stdin {
+            // the provider might have exited, in which case there's no one to tell anything
+            if writeln!(stdin, "{}", message)
+                .and_then(|_| stdin.flush())
+                .is_err()
+            {
+                self.stdin = None;
+            }
+        }
+    }
+}
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        // closing stdin tells the provider to exit, give it a moment to do so
+        self.stdin = None;
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(100) {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_results(stdout: impl BufRead, results: &Mutex<Results>) {
+    for line in stdout.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(_) => break,
+        };
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Invalid message from provider: {}", e);
+                continue;
+            }
+        };
+        let mut results = results.lock().unwrap();
+        // ignore results of old queries
+        if message["query_id"].as_u64() != Some(results.query_id) || results.query_id == 0 {
+            continue;
+        }
+        match message["type"].as_str() {
+            Some("item") => {
+                if let Some(item) = parse_item(&message) {
+                    results.items.retain(|i| i.id != item.id);
+                    results.items.push(item);
+                    results.generation += 1;
+                }
+            }
+            Some("done") => results.done = true,
+            _ => {}
+        }
+    }
+}
+
+fn parse_item(message: &Value) -> Option<ProviderItem> {
+    let string = |value: &Value| value.as_str().map(|s| s.to_owned());
+    let action = &message["action"];
+    Some(ProviderItem {
+        id: match &message["id"] {
+            Value::Number(n) => n.to_string(),
+            id => string(id)?,
+        },
+        label: string(&message["label"])?,
+        comment: string(&message["comment"]).unwrap_or_default(),
+        score: message["score"].as_i64().unwrap_or(0),
+        action: match action["type"].as_str() {
+            Some("run") => ProviderAction::Run(string(&action["command"])?),
+            Some("open") => ProviderAction::Open(string(&action["target"])?),
+            Some("copy") => ProviderAction::Copy(string(&action["text"])?),
+            _ => ProviderAction::None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, remove_file};
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    /// Starts a fake provider running the shell script, with the path of a file as `$1`
+    fn fake_provider(script: &str, name: &str) -> (Provider, PathBuf) {
+        let log = temp_dir().join(format!("rlaunch-provider-{}-{}", process::id(), name));
+        let command = format!("sh -c '{}' sh {}", script, log.display());
+        let provider = Provider::spawn(&command, Duration::from_secs(5)).unwrap();
+        (provider, log)
+    }
+
+    /// Waits until the provider is done with the current query
+    fn wait_until_done(provider: &Provider) -> Vec<ProviderItem> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if provider.results.lock().unwrap().done {
+                return provider.items().1;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("the provider didn't finish the query");
+    }
+
+    /// The messages the provider was sent, after closing its stdin
+    fn sent_messages(provider: Provider, log: &Path) -> Vec<Value> {
+        drop(provider);
+        let contents = read_to_string(log).unwrap();
+        let _ = remove_file(log);
+        contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn items_of_the_last_query() {
+        // answers the first query after the second one is sent, and the second one right away
+        let script = r#"
+            read -r first
+            read -r cancel
+            read -r second
+            echo "{\"type\":\"item\",\"query_id\":1,\"id\":\"old\",\"label\":\"Old\"}"
+            echo "{\"type\":\"item\",\"query_id\":2,\"id\":\"a\",\"label\":\"A\",\"score\":3}"
+            echo "{\"type\":\"item\",\"query_id\":2,\"id\":\"a\",\"label\":\"A2\",\"comment\":\"c\",\"action\":{\"type\":\"copy\",\"text\":\"t\"}}"
+            echo "{\"type\":\"done\",\"query_id\":2}"
+            cat > "$1"
+        "#;
+        let (mut provider, log) = fake_provider(script, "items");
+        provider.query("f");
+        provider.query("fo");
+        let items = wait_until_done(&provider);
+        // items with the same ID replace each other
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, "a");
+        assert_eq!(items[0].label, "A2");
+        assert_eq!(items[0].comment, "c");
+        assert!(matches!(&items[0].action, ProviderAction::Copy(text) if text == "t"));
+
+        provider.activate(&items[0]);
+        let messages = sent_messages(provider, &log);
+        assert_eq!(
+            messages,
+            vec![json!({"type": "activate", "query_id": 2, "id": "a"})]
+        );
+    }
+
+    #[test]
+    fn queries_and_cancels() {
+        let (mut provider, log) = fake_provider(r#"cat > "$1""#, "queries");
+        provider.query("a");
+        provider.query("ab");
+        let messages = sent_messages(provider, &log);
+        assert_eq!(
+            messages,
+            vec![
+                json!({"type": "query", "id": 1, "query": "a"}),
+                json!({"type": "cancel", "id": 1}),
+                json!({"type": "query", "id": 2, "query": "ab"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn cancels_after_the_timeout() {
+        let (mut provider, log) = fake_provider(r#"cat > "$1""#, "timeout");
+        provider.timeout = Duration::from_millis(0);
+        provider.query("slow");
+        provider.check_timeout();
+        // only cancelled once
+        provider.check_timeout();
+        let messages = sent_messages(provider, &log);
+        assert_eq!(
+            messages,
+            vec![
+                json!({"type": "query", "id": 1, "query": "slow"}),
+                json!({"type": "cancel", "id": 1}),
+            ]
+        );
+    }
+
+    #[test]
+    fn item_actions() {
+        let item = parse_item(&json!({
+            "id": 7,
+            "label": "Seven",
+            "action": {"type": "run", "command": "echo 7"},
+        }))
+        .unwrap();
+        assert_eq!(item.id, "7");
+        assert_eq!(item.score, 0);
+        assert!(matches!(item.action, ProviderAction::Run(command) if command == "echo 7"));
+        // the label is required, and so is the action's argument
+        assert!(parse_item(&json!({"id": "x"})).is_none());
+        assert!(
+            parse_item(&json!({"id": "x", "label": "X", "action": {"type": "open"}})).is_none()
+        );
+    }
+}
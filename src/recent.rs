@@ -0,0 +1,43 @@
+use std::env::var;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::PathBuf;
+
+/// How many recently used entries are remembered
+pub const MAX_RECENT: usize = 100;
+
+/// Returns rlaunch's directory in $XDG_CACHE_HOME (or ~/.cache)
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache = match var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(var("HOME").ok()?).join(".cache"),
+    };
+    Some(cache.join("rlaunch"))
+}
+
+/// Reads the recently used entries of the given kind, most recent first
+pub fn read_recent(kind: &str) -> Vec<String> {
+    let path = match cache_dir() {
+        Some(dir) => dir.join(kind),
+        None => return Vec::new(),
+    };
+    match read_to_string(path) {
+        Ok(contents) => contents.lines().map(|line| line.to_owned()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Moves the entry to the top of the recently used entries of the given kind
+pub fn record_recent(kind: &str, entry: &str) {
+    let dir = match cache_dir() {
+        Some(dir) => dir,
+        None => return,
+    };
+    let mut recent = read_recent(kind);
+    recent.retain(|e| e != entry);
+    recent.insert(0, entry.to_owned());
+    recent.truncate(MAX_RECENT);
+
+    if let Err(e) = create_dir_all(&dir).and_then(|_| write(dir.join(kind), recent.join("\n"))) {
+        eprintln!("Couldn't save recently used {}: {}", kind, e);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::applications::exec_with_file;
+use crate::completion::split_command;
+use roxmltree::Document;
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::env::var;
+use std::ffi::OsStr;
+use std::fs::read_to_string;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+/// The namespaces of the bookmarks' metadata
+const BOOKMARK_NAMESPACE: &str = "http://www.freedesktop.org/standards/desktop-bookmarks";
+const MIME_NAMESPACE: &str = "http://www.freedesktop.org/standards/shared-mime-info";
+
+/// A document that was opened recently, as recorded by GTK applications
+#[derive(Debug, Clone)]
+pub struct RecentFile {
+    pub path: PathBuf,
+    pub mime_type: String,
+    /// The name and the command of the application that opened it last
+    pub application: Option<(String, String)>,
+    /// When it was last modified or visited, in microseconds since the epoch
+    pub time: u64,
+}
+
+impl RecentFile {
+    /// The command to open the file with the application that opened it last
+    pub fn command(&self) -> Option<String> {
+        let (_, exec) = self.application.as_ref()?;
+        Some(exec_with_file(exec, &self.path))
+    }
+}
+
+/// Reads the existing files in recently-used.xbel, the most recent first
+pub fn read_recent_files() -> Vec<RecentFile> {
+    let data_dir = match var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match var("HOME") {
+            Ok(home) => PathBuf::from(home).join(".local/share"),
+            Err(_) => return Vec::new(),
+        },
+    };
+    let path = data_dir.join("recently-used.xbel");
+    let contents = match read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return Vec::new(),
+    };
+    let mut files = match parse_xbel(&contents) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Couldn't parse {:?}: {}", path, e);
+            return Vec::new();
+        }
+    };
+    files.retain(|file| file.path.exists());
+    files.sort_by_key(|file| Reverse(file.time));
+    files
+}
+
+/// Reads the bookmarks of local files from the XBEL document
+pub fn parse_xbel(contents: &str) -> Result<Vec<RecentFile>, roxmltree::Error> {
+    let document = Document::parse(contents)?;
+    let mut files = Vec::new();
+    for bookmark in document
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("bookmark"))
+    {
+        let path = match bookmark.attribute("href").and_then(file_path) {
+            Some(path) => path,
+            None => continue,
+        };
+        let time = ["modified", "visited"]
+            .iter()
+            .filter_map(|a| bookmark.attribute(*a).and_then(parse_time))
+            .max()
+            .unwrap_or(0);
+        let mime_type = bookmark
+            .descendants()
+            .find(|n| n.has_tag_name((MIME_NAMESPACE, "mime-type")))
+            .and_then(|n| n.attribute("type"))
+            .unwrap_or("")
+            .to_owned();
+        // the application that opened the file last
+        let application = bookmark
+            .descendants()
+            .filter(|n| n.has_tag_name((BOOKMARK_NAMESPACE, "application")))
+            .max_by_key(|n| n.attribute("modified").and_then(parse_time))
+            .and_then(|n| {
+                let name = n.attribute("name")?;
+                let exec = n.attribute("exec")?;
+                // GLib saves the command quoted like a shell word
+                let exec = match split_command(exec).as_slice() {
+                    [exec] => exec.clone(),
+                    _ => exec.to_owned(),
+                };
+                Some((name.to_owned(), exec))
+            });
+        files.push(RecentFile {
+            path,
+            mime_type,
+            application,
+            time,
+        });
+    }
+    Ok(files)
+}
+
+/// Parses an ISO 8601 date in UTC, like `2024-03-01T12:30:00.123456Z`,
+/// into microseconds since the epoch
+fn parse_time(text: &str) -> Option<u64> {
+    let text = text.strip_suffix('Z').unwrap_or(text);
+    let (date, time) = text.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|n| n.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(|n| n.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    // the fraction can have any number of digits, only microseconds are kept
+    let digits: String = fraction.chars().chain("000000".chars()).take(6).collect();
+    let micros: u64 = digits.parse().ok()?;
+
+    // the days since the epoch in the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146097 + day_of_era - 719468).ok()?;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(seconds * 1_000_000 + micros)
+}
+
+/// The path of a file:// URI, with the %XX escapes decoded
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = path
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match code {
+            Some(code) if bytes[i] == b'%' => {
+                decoded.push(code);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(OsStr::from_bytes(&decoded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_time("2000-03-01T00:00:01Z"),
+            Some(951_868_801_000_000)
+        );
+        assert_eq!(
+            parse_time("2024-02-29T12:30:15.5Z"),
+            Some(1_709_209_815_500_000)
+        );
+        assert_eq!(
+            parse_time("2024-02-29T12:30:15.123456789Z"),
+            Some(1_709_209_815_123_456)
+        );
+        // a tenth of a second later sorts later, unlike as text
+        assert!(parse_time("2024-01-01T00:00:00.9Z") < parse_time("2024-01-01T00:00:01Z"));
+        assert_eq!(parse_time("yesterday"), None);
+        assert_eq!(parse_time("2024-01-01"), None);
+    }
+
+    #[test]
+    fn xbel() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xbel version="1.0"
+      xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks"
+      xmlns:mime="http://www.freedesktop.org/standards/shared-mime-info"
+>
+  <bookmark href="file:///home/user/My%20Notes.txt" added="2024-01-01T10:00:00.5Z" modified="2024-01-02T10:00:00.900000Z" visited="2024-01-02T10:00:00.95Z">
+    <info>
+      <metadata owner="http://freedesktop.org">
+        <mime:mime-type type="text/plain"/>
+        <bookmark:applications>
+          <bookmark:application name="Text Editor" exec="&apos;gnome-text-editor %u&apos;" modified="2024-01-02T10:00:00.9Z" count="2"/>
+          <bookmark:application name="Old Editor" exec="&apos;it&apos;\&apos;&apos;s %u&apos;" modified="2024-01-01T10:00:00Z" count="1"/>
+        </bookmark:applications>
+      </metadata>
+    </info>
+  </bookmark>
+  <bookmark href="file:///tmp/it%27s.pdf" added="2024-01-01T10:00:00Z" modified="2024-01-01T10:00:00Z" visited="2024-01-03T08:00:00Z">
+    <info>
+      <metadata owner="http://freedesktop.org">
+        <mime:mime-type type="application/pdf"/>
+        <bookmark:applications>
+          <bookmark:application name="Viewer" exec="&apos;it&apos;\&apos;&apos;s viewer %u&apos;" modified="2024-01-03T08:00:00Z" count="1"/>
+        </bookmark:applications>
+      </metadata>
+    </info>
+  </bookmark>
+  <bookmark href="https://example.com/" added="2024-01-01T10:00:00Z" modified="2024-01-01T10:00:00Z" visited="2024-01-01T10:00:00Z"/>
+</xbel>
+"#;
+        let files = parse_xbel(contents).unwrap();
+        // only local files
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, PathBuf::from("/home/user/My Notes.txt"));
+        assert_eq!(files[0].mime_type, "text/plain");
+        assert_eq!(
+            files[0].time,
+            parse_time("2024-01-02T10:00:00.95Z").unwrap()
+        );
+        assert_eq!(
+            files[0].application,
+            Some(("Text Editor".to_owned(), "gnome-text-editor %u".to_owned()))
+        );
+        assert_eq!(
+            files[0].command().unwrap(),
+            "gnome-text-editor /home/user/My\\ Notes.txt"
+        );
+
+        assert_eq!(files[1].path, PathBuf::from("/tmp/it's.pdf"));
+        assert_eq!(files[1].time, parse_time("2024-01-03T08:00:00Z").unwrap());
+        // GLib's quoting of single quotes is undone
+        assert_eq!(files[1].application.as_ref().unwrap().1, "it's viewer %u");
+        assert!(files[1].time > files[0].time);
+    }
+}
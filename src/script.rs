@@ -0,0 +1,106 @@
+use crate::completion::split_command;
+use std::process::{Command, Stdio};
+
+/// The separator between an entry and its options, and between the options themselves
+const OPTIONS_SEPARATOR: char = '\0';
+const FIELD_SEPARATOR: char = '\x1f';
+
+#[derive(Debug, Clone, Default)]
+pub struct ScriptEntry {
+    pub text: String,
+    /// Icons are accepted for compatibility, but not shown
+    pub icon: Option<String>,
+    /// Passed back to the script in $ROFI_INFO when the entry is selected
+    pub info: Option<String>,
+    /// Additional text to match the entry by
+    pub meta: Option<String>,
+    pub selectable: bool,
+}
+
+/// A mode whose entries come from an executable, compatible with rofi's script mode:
+///
+/// The script is run without arguments and prints one entry per line, each optionally followed
+/// by `\0option\x1fvalue\x1foption\x1fvalue...` (options: `icon`, `info`, `meta`, `nonselectable`).
+/// When an entry is chosen the script is run again with the entry's text as an argument,
+/// $ROFI_RETV set to 1 (or 2 for custom typed text) and $ROFI_INFO set to the entry's info.
+/// If it prints new entries, they are shown, otherwise rlaunch exits.
+pub struct Script {
+    command: Vec<String>,
+    pub entries: Vec<ScriptEntry>,
+}
+
+impl Script {
+    pub fn new(command: &str) -> Self {
+        let mut script = Self {
+            command: split_command(command),
+            entries: Vec::new(),
+        };
+        script.entries = script.run(None, 0, None);
+        script
+    }
+    /// Runs the script with the chosen entry, or with custom text if no entry was chosen.
+    /// Returns false if the script didn't give any new entries.
+    pub fn select(&mut self, entry: Option<usize>, text: &str) -> bool {
+        self.entries = match entry.and_then(|i| self.entries.get(i)) {
+            Some(entry) => self.run(Some(&entry.text), 1, entry.info.as_deref()),
+            None => self.run(Some(text), 2, None),
+        };
+        !self.entries.is_empty()
+    }
+    fn run(&self, argument: Option<&str>, retv: u32, info: Option<&str>) -> Vec<ScriptEntry> {
+        let (program, arguments) = match self.command.split_first() {
+            Some(c) => c,
+            None => return Vec::new(),
+        };
+        let mut command = Command::new(program);
+        command
+            .args(arguments)
+            .args(argument)
+            .env("ROFI_RETV", retv.to_string())
+            .stdin(Stdio::null());
+        match info {
+            Some(info) => command.env("ROFI_INFO", info),
+            None => command.env_remove("ROFI_INFO"),
+        };
+        match command.output() {
+            Ok(output) => parse_entries(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => {
+                eprintln!("Couldn't run script {:?}: {}", program, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn parse_entries(output: &str) -> Vec<ScriptEntry> {
+    let mut entries = Vec::new();
+    for line in output.lines() {
+        let mut parts = line.splitn(2, OPTIONS_SEPARATOR);
+        let text = parts.next().unwrap_or("");
+        // lines starting with the separator set options of the whole mode, which aren't supported
+        if text.is_empty() {
+            continue;
+        }
+        let mut entry = ScriptEntry {
+            text: text.to_owned(),
+            selectable: true,
+            ..Default::default()
+        };
+        let options: Vec<&str> = parts
+            .next()
+            .map(|options| options.split(FIELD_SEPARATOR).collect())
+            .unwrap_or_default();
+        for option in options.chunks_exact(2) {
+            let value = option[1].to_owned();
+            match option[0] {
+                "icon" => entry.icon = Some(value),
+                "info" => entry.info = Some(value),
+                "meta" => entry.meta = Some(value),
+                "nonselectable" => entry.selectable = value != "true",
+                _ => {}
+            }
+        }
+        entries.push(entry);
+    }
+    entries
+}
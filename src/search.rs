@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+/// The search engines available without configuring any
+const DEFAULT_ENGINES: &[(&str, &str)] = &[
+    ("g", "https://www.google.com/search?q={query}"),
+    ("ddg", "https://duckduckgo.com/?q={query}"),
+    (
+        "wiki",
+        "https://en.wikipedia.org/w/index.php?search={query}",
+    ),
+    ("gh", "https://github.com/search?q={query}"),
+    ("crates", "https://crates.io/search?q={query}"),
+    ("docs", "https://docs.rs/releases/search?query={query}"),
+];
+
+/// Typing the keyword followed by a query searches for it
+#[derive(Debug, Clone)]
+pub struct SearchEngine {
+    pub keyword: String,
+    /// The URL, with `{query}` where the query goes
+    pub template: String,
+}
+
+impl SearchEngine {
+    /// The URL of the search results for the query
+    pub fn url(&self, query: &str) -> String {
+        self.template.replace("{query}", &percent_encode(query))
+    }
+    /// The host of the URL, shown as the name of the search engine
+    pub fn name(&self) -> &str {
+        let url = self.template.splitn(2, "://").last().unwrap_or("");
+        let host = url.split(&['/', '?'][..]).next().unwrap_or("");
+        host.strip_prefix("www.").unwrap_or(host)
+    }
+}
+
+/// Parses the `keyword=template` given in the arguments
+impl FromStr for SearchEngine {
+    type Err = &'static str;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let i = string.find('=').ok_or("Search format: keyword=URL")?;
+        let (keyword, template) = (&string[..i], &string[i + 1..]);
+        // a URL with = but without a keyword would otherwise be taken for one
+        if keyword.is_empty() || keyword.contains(|c: char| c.is_whitespace() || c == '/') {
+            return Err("The search keyword must be a single word");
+        }
+        if !template.contains("{query}") {
+            return Err("The search URL must contain {query}");
+        }
+        Ok(SearchEngine {
+            keyword: keyword.to_owned(),
+            template: template.to_owned(),
+        })
+    }
+}
+
+/// The default search engines, replaced by the configured ones with the same keyword
+pub fn search_engines(configured: &[SearchEngine]) -> Vec<SearchEngine> {
+    let mut engines: Vec<SearchEngine> = DEFAULT_ENGINES
+        .iter()
+        .filter(|(keyword, _)| !configured.iter().any(|e| e.keyword == *keyword))
+        .map(|(keyword, template)| SearchEngine {
+            keyword: (*keyword).to_owned(),
+            template: (*template).to_owned(),
+        })
+        .collect();
+    engines.extend(configured.iter().cloned());
+    engines
+}
+
+/// Finds the search engine whose keyword the text starts with, and the query after it
+pub fn find_engine<'a>(
+    engines: &'a [SearchEngine],
+    text: &'a str,
+) -> Option<(&'a SearchEngine, &'a str)> {
+    let text = text.trim_start();
+    let i = text.find(' ')?;
+    let query = text[i..].trim();
+    if query.is_empty() {
+        return None;
+    }
+    let engine = engines.iter().find(|e| e.keyword == text[..i])?;
+    Some((engine, query))
+}
+
+/// Escapes everything but the unreserved characters of RFC 3986 as %XX
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded += &format!("%{:02X}", byte),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(string: &str) -> Result<SearchEngine, &'static str> {
+        string.parse()
+    }
+
+    #[test]
+    fn encoding() {
+        for (text, expected) in &[
+            ("rust", "rust"),
+            ("a b&c=d", "a%20b%26c%3Dd"),
+            ("-._~", "-._~"),
+            ("100%/?#", "100%25%2F%3F%23"),
+            ("ü", "%C3%BC"),
+        ] {
+            assert_eq!(percent_encode(text), *expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        let aur = engine("aur=https://aur.archlinux.org/packages?K={query}").unwrap();
+        assert_eq!(aur.keyword, "aur");
+        assert_eq!(
+            aur.url("yay bin"),
+            "https://aur.archlinux.org/packages?K=yay%20bin"
+        );
+        // the URL can contain = too
+        assert!(engine("x=https://example.com/?a=b&q={query}").is_ok());
+        for invalid in &[
+            // without the keyword
+            "https://example.com/?q={query}",
+            "=https://example.com/?q={query}",
+            "two words=https://example.com/?q={query}",
+            "x=https://example.com/",
+        ] {
+            assert!(engine(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn names() {
+        for (template, name) in &[
+            ("https://www.google.com/search?q={query}", "google.com"),
+            (
+                "https://en.wikipedia.org/w/index.php?search={query}",
+                "en.wikipedia.org",
+            ),
+            ("https://example.com?q={query}", "example.com"),
+            ("example.com/{query}", "example.com"),
+        ] {
+            let engine = engine(&format!("k={}", template)).unwrap();
+            assert_eq!(engine.name(), *name, "{}", template);
+        }
+    }
+
+    #[test]
+    fn finding() {
+        let engines = search_engines(&[engine("g=https://example.com/?q={query}").unwrap()]);
+        // the configured engine replaces the default one with the same keyword
+        assert_eq!(engines.iter().filter(|e| e.keyword == "g").count(), 1);
+        let (found, query) = find_engine(&engines, "  g  rust  traits ").unwrap();
+        assert_eq!(found.template, "https://example.com/?q={query}");
+        assert_eq!(query, "rust  traits");
+        assert_eq!(find_engine(&engines, "crates tokio").unwrap().1, "tokio");
+        // a keyword alone, or a word that isn't one, isn't a search
+        assert!(find_engine(&engines, "g").is_none());
+        assert!(find_engine(&engines, "g   ").is_none());
+        assert!(find_engine(&engines, "firefox private").is_none());
+        assert!(find_engine(&engines, "google rust").is_none());
+    }
+}
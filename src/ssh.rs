@@ -0,0 +1,286 @@
+use crate::completion::expand;
+use crate::recent::{read_recent, record_recent, MAX_RECENT};
+use std::env::var;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// Includes nested deeper than this are ignored, in case they include each other
+const MAX_INCLUDE_DEPTH: u32 = 16;
+/// Bigger than the difference between any two fuzzy match scores of a host name
+const RECENCY_STEP: i64 = 10000;
+
+#[derive(Debug, Clone)]
+pub struct Host {
+    pub name: String,
+    pub port: Option<u16>,
+    /// Where the host was found
+    pub source: &'static str,
+    /// The position in the recently used hosts, the most recent being 0
+    pub recent: Option<usize>,
+}
+
+impl Host {
+    /// The arguments to give to ssh to connect to this host
+    pub fn ssh_arguments(&self) -> String {
+        match self.port {
+            Some(port) => format!("-p {} {}", port, self.name),
+            None => self.name.clone(),
+        }
+    }
+    /// Added to the score of the host's matches, so that the recently used hosts rank first,
+    /// the most recent first
+    pub fn recency_bonus(&self) -> i64 {
+        match self.recent {
+            Some(position) => (MAX_RECENT - position.min(MAX_RECENT)) as i64 * RECENCY_STEP,
+            None => 0,
+        }
+    }
+    /// The name shown on the bar, also used to remember recently used hosts
+    pub fn display_name(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", self.name, port),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Reads the hosts from the ssh config, known_hosts and /etc/hosts,
+/// with the recently used ones first
+pub fn read_hosts() -> Vec<Host> {
+    let mut hosts = Vec::new();
+    if let Ok(home) = var("HOME") {
+        let ssh_dir = Path::new(&home).join(".ssh");
+        read_config(&ssh_dir.join("config"), &ssh_dir, &mut hosts, 0);
+        read_known_hosts(&ssh_dir.join("known_hosts"), &mut hosts);
+    }
+    read_etc_hosts(&mut hosts);
+
+    // remove duplicates, keeping the first occurence
+    let mut seen = Vec::new();
+    hosts.retain(|host| {
+        let name = host.display_name();
+        if seen.contains(&name) {
+            false
+        } else {
+            seen.push(name);
+            true
+        }
+    });
+
+    // move the recently used hosts to the front, the most recent first
+    let recent = read_recent("ssh");
+    for host in &mut hosts {
+        let name = host.display_name();
+        host.recent = recent.iter().position(|r| *r == name);
+    }
+    hosts.sort_by_key(|host| host.recent.unwrap_or(recent.len()));
+    hosts
+}
+
+/// Remembers the host as recently used
+pub fn record_host(host: &str) {
+    record_recent("ssh", host);
+}
+
+fn read_config(path: &Path, ssh_dir: &Path, hosts: &mut Vec<Host>, depth: u32) {
+    let contents = match read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        // the keyword and arguments may be separated by whitespace or an `=`
+        let (keyword, arguments) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+            Some(i) => (
+                &line[..i],
+                line[i + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == '='),
+            ),
+            None => continue,
+        };
+        if keyword.eq_ignore_ascii_case("Host") {
+            for pattern in arguments.split_whitespace() {
+                // only concrete hosts, not patterns
+                if pattern.contains(['*', '?', '!']) {
+                    continue;
+                }
+                hosts.push(Host {
+                    name: pattern.trim_matches('"').to_owned(),
+                    port: None,
+                    source: "ssh config",
+                    recent: None,
+                });
+            }
+        } else if keyword.eq_ignore_ascii_case("Include") && depth < MAX_INCLUDE_DEPTH {
+            for include in arguments.split_whitespace() {
+                let include = PathBuf::from(expand(include.trim_matches('"')));
+                // relative paths are relative to ~/.ssh
+                let include = if include.is_absolute() {
+                    include
+                } else {
+                    ssh_dir.join(include)
+                };
+                for file in glob_files(&include) {
+                    read_config(&file, ssh_dir, hosts, depth + 1);
+                }
+            }
+        }
+    }
+}
+
+fn read_known_hosts(path: &Path, hosts: &mut Vec<Host>) {
+    let contents = match read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        let mut names = match fields.next() {
+            Some(f) if !f.starts_with('#') => f,
+            _ => continue,
+        };
+        // skip markers like @cert-authority
+        if names.starts_with('@') {
+            names = match fields.next() {
+                Some(f) => f,
+                None => continue,
+            };
+        }
+        for name in names.split(',') {
+            // hashed hosts can't be recovered, and negated ones aren't hosts
+            if name.starts_with('|') || name.starts_with('!') || name.contains('*') {
+                continue;
+            }
+            // hosts on non-standard ports are written as [host]:port
+            let (name, port) = match name.strip_prefix('[').and_then(|n| n.split_once("]:")) {
+                Some((name, port)) => (name, port.parse().ok()),
+                None => (name, None),
+            };
+            hosts.push(Host {
+                name: name.to_owned(),
+                port,
+                source: "known_hosts",
+                recent: None,
+            });
+        }
+    }
+}
+
+fn read_etc_hosts(hosts: &mut Vec<Host>) {
+    let contents = match read_to_string("/etc/hosts") {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let address = match fields.next() {
+            Some(a) => a,
+            None => continue,
+        };
+        // nobody wants to ssh into the loopback or multicast addresses
+        if address.starts_with("127.")
+            || address == "::1"
+            || address.starts_with("fe00:")
+            || address.starts_with("ff0")
+        {
+            continue;
+        }
+        for name in fields {
+            hosts.push(Host {
+                name: name.to_owned(),
+                port: None,
+                source: "/etc/hosts",
+                recent: None,
+            });
+        }
+    }
+}
+
+/// Expands wildcards in the last component of the path
+fn glob_files(path: &Path) -> Vec<PathBuf> {
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return Vec::new(),
+    };
+    if !file_name.contains(['*', '?']) {
+        return vec![path.to_owned()];
+    }
+    let dir = match path.parent() {
+        Some(dir) => dir,
+        None => return Vec::new(),
+    };
+    let mut files: Vec<PathBuf> = match read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| wildcard_match(&file_name, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+/// Matches the text against a pattern with `*` (any number of characters) and `?` (one character)
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and the text position it matched up to
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last `*` match one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuzzy_matcher::skim::SkimMatcherV2;
+    use fuzzy_matcher::FuzzyMatcher;
+
+    #[test]
+    fn recent_hosts_rank_first() {
+        let host = |name: &str, recent| Host {
+            name: name.to_owned(),
+            port: None,
+            source: "ssh config",
+            recent,
+        };
+        let hosts = [
+            host("server", None),
+            host("old-server.example.com", Some(3)),
+            host("s", Some(0)),
+        ];
+        let matcher = SkimMatcherV2::default();
+        let mut scores: Vec<(i64, &str)> = hosts
+            .iter()
+            .map(|h| {
+                (
+                    matcher.fuzzy_match(&h.name, "s").unwrap() + h.recency_bonus(),
+                    &h.name[..],
+                )
+            })
+            .collect();
+        scores.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        let names: Vec<&str> = scores.iter().map(|(_, name)| *name).collect();
+        assert_eq!(names, vec!["s", "old-server.example.com", "server"]);
+    }
+}
@@ -0,0 +1,56 @@
+/// Generated by scripts/generate-unicode-table.py, one character per line:
+/// `characters<TAB>name<TAB>keywords`, with the keywords separated by `|`
+const TABLE: &str = include_str!("unicode.txt");
+
+#[derive(Debug, Clone)]
+pub struct Character {
+    /// Emoji can be made of several code points
+    pub characters: &'static str,
+    pub name: &'static str,
+    pub keywords: Vec<&'static str>,
+}
+
+/// Reads the embedded table of characters, emoji first
+pub fn read_characters() -> Vec<Character> {
+    TABLE
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(Character {
+                characters: fields.next()?,
+                name: fields.next()?,
+                keywords: fields
+                    .next()
+                    .unwrap_or("")
+                    .split('|')
+                    .filter(|k| !k.is_empty())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table() {
+        let characters = read_characters();
+        assert_eq!(characters[0].characters, "😀");
+        assert_eq!(characters[0].name, "grinning face");
+        let euro = characters.iter().find(|c| c.characters == "€").unwrap();
+        assert_eq!(euro.name, "euro sign");
+    }
+
+    #[test]
+    #[ignore = "src/unicode.txt has to be regenerated with the CLDR annotations"]
+    fn emoji_have_keywords() {
+        let characters = read_characters();
+        let grinning = characters.iter().find(|c| c.characters == "😀").unwrap();
+        assert!(grinning.keywords.contains(&"grin"));
+        // most emoji have keywords, so an empty column can't go unnoticed
+        let with_keywords = characters.iter().filter(|c| !c.keywords.is_empty()).count();
+        assert!(with_keywords > 1000);
+    }
+}
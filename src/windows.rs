@@ -0,0 +1,52 @@
+use crate::applications::App;
+use crate::x11::X11Context;
+use std::os::raw::c_ulong;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct WindowInfo {
+    pub id: c_ulong,
+    pub title: String,
+    pub class: String,
+    pub desktop: Option<u32>,
+}
+
+impl WindowInfo {
+    /// The text shown next to the window title
+    pub fn description(&self) -> String {
+        match self.desktop {
+            Some(desktop) => format!("{} (desktop {})", self.class, desktop + 1),
+            None => self.class.clone(),
+        }
+    }
+}
+
+/// Reads all windows managed by the window manager (EWMH compliant window managers only)
+pub fn read_windows(xc: &X11Context) -> Vec<WindowInfo> {
+    xc.get_client_list()
+        .into_iter()
+        .map(|id| WindowInfo {
+            id,
+            title: xc.get_window_name(id),
+            class: xc.get_window_class(id),
+            desktop: xc.get_window_desktop(id),
+        })
+        .collect()
+}
+
+/// Finds an open window of the given application, either by its StartupWMClass
+/// or, if it doesn't have one, by the name of the executable
+pub fn find_app_window<'a>(windows: &'a [WindowInfo], app: &App) -> Option<&'a WindowInfo> {
+    let class = if app.wm_class.is_empty() {
+        let executable = app.exec.split_whitespace().next()?;
+        Path::new(executable)
+            .file_name()?
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        app.wm_class.clone()
+    };
+    windows
+        .iter()
+        .find(|window| window.class.eq_ignore_ascii_case(&class))
+}
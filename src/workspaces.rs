@@ -0,0 +1,35 @@
+use crate::x11::X11Context;
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub index: u32,
+    pub name: String,
+    pub current: bool,
+}
+
+impl Workspace {
+    /// The text shown next to the workspace name
+    pub fn description(&self) -> String {
+        if self.current {
+            format!("desktop {} (current)", self.index + 1)
+        } else {
+            format!("desktop {}", self.index + 1)
+        }
+    }
+}
+
+/// Reads all desktops of an EWMH compliant window manager, naming the unnamed ones by their number
+pub fn read_workspaces(xc: &X11Context) -> Vec<Workspace> {
+    let names = xc.get_desktop_names();
+    let current = xc.get_current_desktop();
+    (0..xc.get_number_of_desktops())
+        .map(|index| Workspace {
+            index,
+            name: match names.get(index as usize) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => format!("Desktop {}", index + 1),
+            },
+            current: current == Some(index),
+        })
+        .collect()
+}
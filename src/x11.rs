@@ -1,11 +1,14 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::c_ulong;
 use std::ffi::CString;
 use std::mem::MaybeUninit;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_long, c_uchar};
 use std::ptr::{null, null_mut};
+use std::slice;
 use std::thread::sleep;
 use std::time::Duration;
-use x11_dl::{xft, xinerama, xlib};
-use std::ffi::c_ulong;
+use x11_dl::{xft, xinerama, xlib, xtest};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Action {
@@ -25,6 +28,12 @@ pub struct TextRenderingContext {
     visual: *mut xlib::Visual,
     cmap: c_ulong,
     font: *mut xft::XftFont,
+    /// The name the font was opened with, to find similar fonts for the characters it doesn't have
+    font_name: String,
+    /// Fonts opened for characters that the main font doesn't have
+    fallbacks: RefCell<Vec<*mut xft::XftFont>>,
+    /// Characters that no font has, so that they're not looked for again
+    missing: RefCell<HashSet<char>>,
     colors: Vec<xft::XftColor>,
     draw: *mut xft::XftDraw,
 }
@@ -144,6 +153,14 @@ impl X11Context {
             (self.xlib.XMapRaised)(self.display, window.window);
         }
     }
+    /// hides the window and releases the keyboard, keeping the connection open
+    pub fn hide_window(&self, window: &Window) {
+        unsafe {
+            (self.xlib.XUngrabKeyboard)(self.display, xlib::CurrentTime);
+            (self.xlib.XUnmapWindow)(self.display, window.window);
+            (self.xlib.XFlush)(self.display);
+        }
+    }
     pub fn grab_keyboard(&self) {
         for _ in 0..1000 {
             if unsafe {
@@ -165,9 +182,9 @@ impl X11Context {
             }
         }
     }
-    pub fn init_trc(&self, window: &Window, font: &str) -> TextRenderingContext {
+    pub fn init_trc(&self, window: &Window, font_name: &str) -> TextRenderingContext {
         unsafe {
-            let cfontname = CString::new(font).unwrap();
+            let cfontname = CString::new(font_name).unwrap();
 
             let screen = (self.xlib.XDefaultScreen)(self.display);
             let visual = (self.xlib.XDefaultVisual)(self.display, screen);
@@ -181,6 +198,9 @@ impl X11Context {
                 visual,
                 cmap,
                 font,
+                font_name: font_name.to_owned(),
+                fallbacks: RefCell::new(Vec::new()),
+                missing: RefCell::new(HashSet::new()),
                 colors,
                 draw,
             }
@@ -213,6 +233,7 @@ impl X11Context {
             }
         }
     }
+    #[allow(clippy::needless_borrow)]
     pub fn run<F>(&self, mut handle_events: F)
     where
         F: FnMut(&Self, Option<&xlib::XEvent>) -> Action,
@@ -261,37 +282,52 @@ impl X11Context {
         y: i32,
         text: &str,
     ) {
-        unsafe {
-            let ctext = CString::new(text).unwrap();
+        let mut x = x;
+        for (font, run) in self.font_runs(trc, text) {
+            unsafe {
+                let ctext = text_to_cstring(run);
 
-            // render the text
-            let mut col = trc.colors[color];
-            (self.xft.XftDrawStringUtf8)(
-                trc.draw,
-                &mut col,
-                trc.font,
-                x,
-                y,
-                ctext.as_ptr() as *mut u8,
-                text.len() as i32,
-            );
+                // render the text
+                let mut col = trc.colors[color];
+                (self.xft.XftDrawStringUtf8)(
+                    trc.draw,
+                    &mut col,
+                    font,
+                    x,
+                    y,
+                    ctext.as_ptr() as *mut u8,
+                    ctext.as_bytes().len() as i32,
+                );
+            }
+            x += self.get_run_extents(font, run).xOff as i32;
         }
     }
     pub fn get_text_dimensions(&self, trc: &TextRenderingContext, text: &str) -> (u16, u16) {
+        let runs = self.font_runs(trc, text);
+        // text with characters from other fonts is measured by how far each run advances
+        if runs.iter().any(|(font, _)| *font != trc.font) {
+            let mut width = 0;
+            let mut height = 0;
+            for (font, run) in runs {
+                let extents = self.get_run_extents(font, run);
+                width += extents.xOff as i32;
+                height = height.max(extents.height);
+            }
+            return (width as u16, height);
+        }
         unsafe {
             // Some fonts treat a single space at the end weirdly
             // which makes typing a bit confusing, so we will add a '/'
             // to the end and then remove it's width from the total width
             let dot = CString::new("/").unwrap();
-            let owned_text = text.to_owned();
-            let ctext = CString::new(owned_text + "/").unwrap();
+            let ctext = text_to_cstring(&(text.to_owned() + "/"));
 
             let mut total_ext = MaybeUninit::zeroed().assume_init();
             (self.xft.XftTextExtentsUtf8)(
                 self.display,
                 trc.font,
                 ctext.as_ptr() as *mut u8,
-                text.len() as i32 + 1,
+                ctext.as_bytes().len() as i32,
                 &mut total_ext,
             );
             let mut dot_ext = MaybeUninit::zeroed().assume_init();
@@ -306,6 +342,149 @@ impl X11Context {
             (total_ext.width - dot_ext.width, total_ext.height)
         }
     }
+    fn get_run_extents(&self, font: *mut xft::XftFont, run: &str) -> x11_dl::xrender::XGlyphInfo {
+        unsafe {
+            let ctext = text_to_cstring(run);
+            let mut extents = MaybeUninit::zeroed().assume_init();
+            (self.xft.XftTextExtentsUtf8)(
+                self.display,
+                font,
+                ctext.as_ptr() as *mut u8,
+                ctext.as_bytes().len() as i32,
+                &mut extents,
+            );
+            extents
+        }
+    }
+    /// Splits the text into runs drawn with the same font,
+    /// using fallback fonts for the characters that the main font doesn't have
+    fn font_runs<'a>(
+        &self,
+        trc: &TextRenderingContext,
+        text: &'a str,
+    ) -> Vec<(*mut xft::XftFont, &'a str)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut current = trc.font;
+        for (i, c) in text.char_indices() {
+            // joiners and variation selectors belong to the characters around them
+            if matches!(c, '\u{200D}' | '\u{FE0E}' | '\u{FE0F}') {
+                continue;
+            }
+            let font = self.font_for_char(trc, c);
+            if font != current {
+                if i > start {
+                    runs.push((current, &text[start..i]));
+                }
+                start = i;
+                current = font;
+            }
+        }
+        if start < text.len() {
+            runs.push((current, &text[start..]));
+        }
+        runs
+    }
+    fn font_for_char(&self, trc: &TextRenderingContext, c: char) -> *mut xft::XftFont {
+        let has_char =
+            |font| unsafe { (self.xft.XftCharExists)(self.display, font, c as u32) != 0 };
+        if has_char(trc.font) || trc.missing.borrow().contains(&c) {
+            return trc.font;
+        }
+        let mut fallbacks = trc.fallbacks.borrow_mut();
+        if let Some(font) = fallbacks.iter().find(|font| has_char(**font)) {
+            return *font;
+        }
+
+        // ask fontconfig for a font like the main one, but with the character
+        let name = CString::new(format!("{}:charset={:x}", trc.font_name, c as u32)).unwrap();
+        let font = unsafe {
+            let screen = (self.xlib.XDefaultScreen)(self.display);
+            // the parsed pattern is small and only made once per character, so it's not freed
+            let pattern = (self.xft.XftNameParse)(name.as_ptr());
+            let mut result = xft::FcResult::NoMatch;
+            let matched = (self.xft.XftFontMatch)(self.display, screen, pattern, &mut result);
+            if matched.is_null() {
+                null_mut()
+            } else {
+                // the font takes the ownership of the matched pattern
+                (self.xft.XftFontOpenPattern)(self.display, matched)
+            }
+        };
+        if font.is_null() || !has_char(font) {
+            if !font.is_null() {
+                unsafe { (self.xft.XftFontClose)(self.display, font) };
+            }
+            trc.missing.borrow_mut().insert(c);
+            return trc.font;
+        }
+        fallbacks.push(font);
+        font
+    }
+    /// Types the text into the focused window with fake key presses,
+    /// temporarily mapping each character to an unused keycode
+    pub fn type_text(&self, text: &str) {
+        let xtest = match xtest::Xf86vmode::open() {
+            Ok(x) => x,
+            Err(_) => {
+                eprintln!("Failed to load XTest.");
+                return;
+            }
+        };
+        unsafe {
+            let (mut min, mut max) = (0, 0);
+            (self.xlib.XDisplayKeycodes)(self.display, &mut min, &mut max);
+            let mut per_keycode = 0;
+            let mapping = (self.xlib.XGetKeyboardMapping)(
+                self.display,
+                min as c_uchar,
+                max - min + 1,
+                &mut per_keycode,
+            );
+            let mapping_slice =
+                slice::from_raw_parts(mapping, ((max - min + 1) * per_keycode) as usize);
+            // a keycode without any keysyms can be used without changing how the keyboard works
+            let free = mapping_slice
+                .chunks(per_keycode as usize)
+                .position(|keysyms| keysyms.iter().all(|k| *k == 0));
+            (self.xlib.XFree)(mapping as *mut _);
+            let keycode = match free {
+                Some(i) => min + i as i32,
+                None => {
+                    eprintln!("No free keycode to type with.");
+                    return;
+                }
+            };
+
+            for c in text.chars() {
+                // Unicode keysyms are the code point with this bit set
+                let mut keysyms = [0x0100_0000 | c as c_ulong; 2];
+                (self.xlib.XChangeKeyboardMapping)(
+                    self.display,
+                    keycode,
+                    2,
+                    keysyms.as_mut_ptr(),
+                    1,
+                );
+                (self.xlib.XSync)(self.display, xlib::False);
+                (xtest.XTestFakeKeyEvent)(self.display, keycode as u32, xlib::True, 0);
+                (xtest.XTestFakeKeyEvent)(self.display, keycode as u32, xlib::False, 0);
+                (self.xlib.XSync)(self.display, xlib::False);
+                // the window has to look the keysym up before it's changed again
+                sleep(Duration::from_millis(20));
+            }
+
+            let mut no_keysyms = [0; 2];
+            (self.xlib.XChangeKeyboardMapping)(
+                self.display,
+                keycode,
+                2,
+                no_keysyms.as_mut_ptr(),
+                1,
+            );
+            (self.xlib.XSync)(self.display, xlib::False);
+        }
+    }
     pub fn keyevent_to_char(&self, mut keyevent: xlib::XKeyEvent) -> char {
         unsafe {
             let mut c_char: c_char = 0;
@@ -319,6 +498,269 @@ impl X11Context {
             _ => None,
         }
     }
+    /// takes the ownership of the clipboard and serves the text to anyone who asks for it.
+    /// X11 clipboards only live as long as their owner, so this blocks until some other
+    /// client takes the ownership.
+    pub fn serve_clipboard(&self, window: &Window, text: &str) {
+        let clipboard = self.get_atom("CLIPBOARD");
+        let targets = self.get_atom("TARGETS");
+        let utf8_string = self.get_atom("UTF8_STRING");
+        unsafe {
+            (self.xlib.XSetSelectionOwner)(
+                self.display,
+                clipboard,
+                window.window,
+                xlib::CurrentTime,
+            );
+            if (self.xlib.XGetSelectionOwner)(self.display, clipboard) != window.window {
+                return;
+            }
+
+            let mut event = MaybeUninit::<xlib::XEvent>::uninit();
+            loop {
+                (self.xlib.XNextEvent)(self.display, event.as_mut_ptr());
+                let event = event.assume_init();
+                match event.get_type() {
+                    xlib::SelectionClear => break,
+                    xlib::SelectionRequest => {
+                        let request = event.selection_request;
+                        // obsolete clients don't give a property
+                        let property = if request.property == 0 {
+                            request.target
+                        } else {
+                            request.property
+                        };
+                        let mut reply: xlib::XEvent = MaybeUninit::zeroed().assume_init();
+                        reply.selection.type_ = xlib::SelectionNotify;
+                        reply.selection.requestor = request.requestor;
+                        reply.selection.selection = request.selection;
+                        reply.selection.target = request.target;
+                        reply.selection.time = request.time;
+                        reply.selection.property = property;
+
+                        if request.target == targets {
+                            let supported = [targets, utf8_string, xlib::XA_STRING];
+                            (self.xlib.XChangeProperty)(
+                                self.display,
+                                request.requestor,
+                                property,
+                                xlib::XA_ATOM,
+                                32,
+                                xlib::PropModeReplace,
+                                supported.as_ptr() as *const c_uchar,
+                                supported.len() as i32,
+                            );
+                        } else if request.target == utf8_string || request.target == xlib::XA_STRING
+                        {
+                            (self.xlib.XChangeProperty)(
+                                self.display,
+                                request.requestor,
+                                property,
+                                request.target,
+                                8,
+                                xlib::PropModeReplace,
+                                text.as_ptr(),
+                                text.len() as i32,
+                            );
+                        } else {
+                            // unsupported target, refuse
+                            reply.selection.property = 0;
+                        }
+                        (self.xlib.XSendEvent)(
+                            self.display,
+                            request.requestor,
+                            xlib::False,
+                            0,
+                            &mut reply,
+                        );
+                        (self.xlib.XFlush)(self.display);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    /// returns the client windows managed by the window manager, in the initial mapping order
+    pub fn get_client_list(&self) -> Vec<c_ulong> {
+        self.get_long_property(self.root, "_NET_CLIENT_LIST", xlib::XA_WINDOW)
+    }
+    pub fn get_window_name(&self, window: c_ulong) -> String {
+        let utf8_string = self.get_atom("UTF8_STRING");
+        let name = self.get_property(window, "_NET_WM_NAME", utf8_string);
+        let name = match name {
+            Some((8, name)) => name,
+            // fall back to the ICCCM name if the EWMH one is not set
+            _ => match self.get_property(window, "WM_NAME", xlib::XA_STRING) {
+                Some((8, name)) => name,
+                _ => Vec::new(),
+            },
+        };
+        String::from_utf8_lossy(&name).into_owned()
+    }
+    /// returns the class part of WM_CLASS (the second of the two null-terminated strings)
+    pub fn get_window_class(&self, window: c_ulong) -> String {
+        match self.get_property(window, "WM_CLASS", xlib::XA_STRING) {
+            Some((8, class)) => class
+                .split(|b| *b == 0)
+                .nth(1)
+                .map(|class| String::from_utf8_lossy(class).into_owned())
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+    /// returns None if the window is shown on all desktops or the desktop is unknown
+    pub fn get_window_desktop(&self, window: c_ulong) -> Option<u32> {
+        match self
+            .get_long_property(window, "_NET_WM_DESKTOP", xlib::XA_CARDINAL)
+            .first()
+        {
+            Some(&desktop) if desktop != 0xFFFF_FFFF => Some(desktop as u32),
+            _ => None,
+        }
+    }
+    /// asks the window manager to switch to the window's desktop, raise it and focus it
+    pub fn activate_window(&self, window: c_ulong) {
+        // source indication 2 means the request comes from a pager,
+        // which window managers don't subject to focus stealing prevention
+        self.send_root_message(window, "_NET_ACTIVE_WINDOW", [2, 0, 0, 0, 0]);
+    }
+    /// returns the window that was focused before rlaunch was started
+    pub fn get_active_window(&self) -> Option<c_ulong> {
+        match self
+            .get_long_property(self.root, "_NET_ACTIVE_WINDOW", xlib::XA_WINDOW)
+            .first()
+        {
+            Some(&window) if window != 0 => Some(window),
+            _ => None,
+        }
+    }
+    pub fn get_number_of_desktops(&self) -> u32 {
+        self.get_long_property(self.root, "_NET_NUMBER_OF_DESKTOPS", xlib::XA_CARDINAL)
+            .first()
+            .map(|&n| n as u32)
+            .unwrap_or(0)
+    }
+    pub fn get_current_desktop(&self) -> Option<u32> {
+        self.get_long_property(self.root, "_NET_CURRENT_DESKTOP", xlib::XA_CARDINAL)
+            .first()
+            .map(|&desktop| desktop as u32)
+    }
+    /// returns the names of the desktops, which may be fewer than the number of desktops
+    pub fn get_desktop_names(&self) -> Vec<String> {
+        let utf8_string = self.get_atom("UTF8_STRING");
+        match self.get_property(self.root, "_NET_DESKTOP_NAMES", utf8_string) {
+            Some((8, names)) => {
+                let mut names: Vec<String> = names
+                    .split(|b| *b == 0)
+                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                    .collect();
+                // the last name is null-terminated too
+                if names.last().map(|name| name.is_empty()).unwrap_or(false) {
+                    names.pop();
+                }
+                names
+            }
+            _ => Vec::new(),
+        }
+    }
+    pub fn switch_to_desktop(&self, desktop: u32) {
+        self.send_root_message(
+            self.root,
+            "_NET_CURRENT_DESKTOP",
+            [desktop as c_long, 0, 0, 0, 0],
+        );
+    }
+    pub fn move_window_to_desktop(&self, window: c_ulong, desktop: u32) {
+        self.send_root_message(window, "_NET_WM_DESKTOP", [desktop as c_long, 2, 0, 0, 0]);
+    }
+    fn send_root_message(&self, window: c_ulong, message_type: &str, data: [c_long; 5]) {
+        unsafe {
+            let mut event: xlib::XEvent = MaybeUninit::zeroed().assume_init();
+            event.client_message.type_ = xlib::ClientMessage;
+            event.client_message.window = window;
+            event.client_message.message_type = self.get_atom(message_type);
+            event.client_message.format = 32;
+            for (i, value) in data.iter().enumerate() {
+                event.client_message.data.set_long(i, *value);
+            }
+            (self.xlib.XSendEvent)(
+                self.display,
+                self.root,
+                xlib::False,
+                xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+                &mut event,
+            );
+            (self.xlib.XFlush)(self.display);
+        }
+    }
+    fn get_atom(&self, name: &str) -> c_ulong {
+        let cname = CString::new(name).unwrap();
+        unsafe { (self.xlib.XInternAtom)(self.display, cname.as_ptr(), xlib::False) }
+    }
+    /// reads a property with the format 32, whose items Xlib stores as longs
+    fn get_long_property(
+        &self,
+        window: c_ulong,
+        property: &str,
+        prop_type: c_ulong,
+    ) -> Vec<c_ulong> {
+        match self.get_property(window, property, prop_type) {
+            Some((32, data)) => data
+                .chunks_exact(std::mem::size_of::<c_ulong>())
+                .map(|chunk| {
+                    let mut bytes = [0; std::mem::size_of::<c_ulong>()];
+                    bytes.copy_from_slice(chunk);
+                    c_ulong::from_ne_bytes(bytes)
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+    /// returns the format and the raw data of the property, if it exists and has the given type
+    fn get_property(
+        &self,
+        window: c_ulong,
+        property: &str,
+        prop_type: c_ulong,
+    ) -> Option<(i32, Vec<u8>)> {
+        unsafe {
+            let mut actual_type = 0;
+            let mut format = 0;
+            let mut items = 0;
+            let mut bytes_after = 0;
+            let mut data: *mut c_uchar = null_mut();
+            let status = (self.xlib.XGetWindowProperty)(
+                self.display,
+                window,
+                self.get_atom(property),
+                0,
+                // the length is in 32-bit multiples
+                c_long::MAX / 4,
+                xlib::False,
+                prop_type,
+                &mut actual_type,
+                &mut format,
+                &mut items,
+                &mut bytes_after,
+                &mut data,
+            );
+            if status != xlib::Success as i32 || data.is_null() {
+                return None;
+            }
+            let item_size = match format {
+                8 => 1,
+                16 => std::mem::size_of::<std::os::raw::c_short>(),
+                32 => std::mem::size_of::<c_long>(),
+                _ => 0,
+            };
+            let bytes = slice::from_raw_parts(data, items as usize * item_size).to_vec();
+            (self.xlib.XFree)(data as *mut _);
+            if actual_type != prop_type {
+                return None;
+            }
+            Some((format, bytes))
+        }
+    }
 }
 
 impl Drop for X11Context {
@@ -328,3 +770,9 @@ impl Drop for X11Context {
         }
     }
 }
+
+/// The text as a C string, leaving out the NUL characters it can't contain,
+/// since the shown text can come from other programs
+fn text_to_cstring(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap()
+}